@@ -0,0 +1,523 @@
+use std::collections::BTreeMap;
+
+use super::token::Token;
+use super::ParseError;
+
+/// How an argument is given in the command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+    /// Given by its position, once every named argument have been removed
+    Positional,
+    /// Takes all the remaining positional values
+    Variadic,
+    /// Given by its name: `---<name> <value>`
+    Named,
+}
+
+/// What an argument accepts as value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Type {
+    Text,
+    Number,
+    Path,
+    Directory,
+    /// A program, found in the `$PATH`
+    Executable,
+    /// An environment variable and its value: `KEY=VALUE`
+    Assignment,
+    /// A pane of the session, by its id, its title or its command
+    Pane,
+    /// A tab of the session, by its name or its position from 1
+    Tab,
+    /// A named argument without value, it is either present or not
+    Switch,
+}
+
+impl Type {
+    /// Short name of the value, used in the error messages and the usage
+    pub(crate) fn placeholder(&self) -> &'static str {
+        match self {
+            Type::Text => "TEXT",
+            Type::Number => "N",
+            Type::Path => "PATH",
+            Type::Directory => "DIR",
+            Type::Executable => "PROGRAM",
+            Type::Assignment => "KEY=VALUE",
+            Type::Pane => "PANE",
+            Type::Tab => "TAB",
+            Type::Switch => "",
+        }
+    }
+}
+
+/// Declaration of one argument of an action
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Argument {
+    pub(crate) name: &'static str,
+    pub(crate) kind: Kind,
+    pub(crate) value: Type,
+    pub(crate) required: bool,
+    /// The value used when the argument is not given
+    pub(crate) default: Option<&'static str>,
+    pub(crate) doc: &'static str,
+}
+
+impl Argument {
+    pub(crate) const fn positional(name: &'static str, value: Type, doc: &'static str) -> Self {
+        Argument {
+            name,
+            kind: Kind::Positional,
+            value,
+            required: false,
+            default: None,
+            doc,
+        }
+    }
+
+    pub(crate) const fn variadic(name: &'static str, value: Type, doc: &'static str) -> Self {
+        Argument {
+            name,
+            kind: Kind::Variadic,
+            value,
+            required: false,
+            default: None,
+            doc,
+        }
+    }
+
+    pub(crate) const fn named(name: &'static str, value: Type, doc: &'static str) -> Self {
+        Argument {
+            name,
+            kind: Kind::Named,
+            value,
+            required: false,
+            default: None,
+            doc,
+        }
+    }
+
+    pub(crate) const fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub(crate) const fn default(mut self, default: &'static str) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    /// What the argument is for, and its default value
+    pub(crate) fn description(&self) -> String {
+        match self.default {
+            Some(default) => format!("{}, `{default}` by default", self.doc),
+            None => self.doc.to_string(),
+        }
+    }
+
+    /// Name as shown to the user: `COMMAND` or `---cwd`
+    pub(crate) fn label(&self) -> String {
+        match self.kind {
+            Kind::Positional | Kind::Variadic => self.name.to_uppercase(),
+            Kind::Named => format!("---{}", self.name),
+        }
+    }
+
+    /// How to write this argument: `<COMMAND>`, `[ARGS...]` or `[---cwd DIR]`
+    pub(crate) fn usage(&self) -> String {
+        let usage = match (self.kind, self.value) {
+            (Kind::Positional, _) => self.name.to_uppercase(),
+            (Kind::Variadic, _) => format!("{}...", self.name.to_uppercase()),
+            (Kind::Named, Type::Switch) => self.label(),
+            (Kind::Named, value) => format!("{} {}", self.label(), value.placeholder()),
+        };
+
+        if self.required {
+            format!("<{usage}>")
+        } else {
+            format!("[{usage}]")
+        }
+    }
+}
+
+/// The values given to the arguments of an action, by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Values {
+    values: BTreeMap<&'static str, Vec<String>>,
+    /// Whether each positional value was written after a `--`
+    literal: BTreeMap<&'static str, Vec<bool>>,
+    defaults: BTreeMap<&'static str, &'static str>,
+}
+
+impl Values {
+    /// The value of a single argument, or its default
+    pub(crate) fn get(&self, name: &str) -> Option<String> {
+        self.values
+            .get(name)
+            .and_then(|v| v.last().cloned())
+            .or_else(|| self.defaults.get(name).map(|d| d.to_string()))
+    }
+
+    pub(crate) fn get_all(&self, name: &str) -> Vec<String> {
+        self.values.get(name).cloned().unwrap_or_default()
+    }
+
+    /// The values of a positional argument, with whether each one must be taken as is
+    pub(crate) fn get_all_literal(&self, name: &str) -> Vec<(String, bool)> {
+        let literal = self.literal.get(name).cloned().unwrap_or_default();
+        self.get_all(name).into_iter().zip(literal).collect()
+    }
+
+    pub(crate) fn get_number(&self, name: &str) -> Option<usize> {
+        self.get(name).and_then(|v| v.parse().ok())
+    }
+
+    /// Whether the argument is given, its default does not count
+    pub(crate) fn is_set(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+}
+
+/// Assign each token to the argument it belongs to.
+///
+/// Named arguments are recognized with `---<name>` anywhere, so they never clash with the options of a program given to `Run`.
+/// The shorter `--<name>` is also accepted, except after the program given to an action running one:
+/// `Run cargo --cwd` gives `--cwd` to `cargo`, but `Edit a.rs --line 3` is read as `Edit a.rs ---line 3`.
+/// A lone `--` ends the named arguments, it is dropped unless it is given to a program: `Run git diff -- src`.
+pub(crate) fn bind(arguments: &[Argument], tokens: &[Token]) -> Result<Values, ParseError> {
+    let mut values = Values {
+        defaults: arguments
+            .iter()
+            .filter_map(|a| a.default.map(|default| (a.name, default)))
+            .collect(),
+        ..Values::default()
+    };
+
+    let mut positionals = arguments
+        .iter()
+        .filter(|a| matches!(a.kind, Kind::Positional | Kind::Variadic));
+    let mut current_positional = positionals.next();
+    let runs_program = runs_program(arguments);
+    let mut positional_seen = false;
+
+    let mut tokens = tokens.iter();
+    while let Some(token) = tokens.next() {
+        if ends_own_options(token, runs_program && positional_seen) {
+            continue;
+        }
+        if let Some(named) = named_argument(arguments, token, runs_program && positional_seen)? {
+            let value = if let Type::Switch = named.value {
+                String::new()
+            } else {
+                let value = tokens.next().ok_or_else(|| ParseError {
+                    message: format!(
+                        "`{}` expects a {}",
+                        named.label(),
+                        named.value.placeholder()
+                    ),
+                    span: token.span.clone(),
+                })?;
+                check_type(named, value)?;
+                value.value.clone()
+            };
+            values.values.entry(named.name).or_default().push(value);
+            continue;
+        }
+
+        let Some(positional) = current_positional else {
+            return Err(ParseError {
+                message: format!("Unexpected argument `{}`", token.value),
+                span: token.span.clone(),
+            });
+        };
+        check_type(positional, token)?;
+        values
+            .values
+            .entry(positional.name)
+            .or_default()
+            .push(token.value.clone());
+        values
+            .literal
+            .entry(positional.name)
+            .or_default()
+            .push(token.literal);
+
+        positional_seen = true;
+        if positional.kind != Kind::Variadic {
+            current_positional = positionals.next();
+        }
+    }
+
+    Ok(values)
+}
+
+/// The argument the token at `index` is a value of, following the same rules as `bind`.
+/// `index` can be the length of `tokens` for the next value to be written.
+pub(crate) fn argument_of<'a>(
+    arguments: &'a [Argument],
+    tokens: &[Token],
+    index: usize,
+) -> Option<&'a Argument> {
+    let mut positionals = arguments
+        .iter()
+        .filter(|a| matches!(a.kind, Kind::Positional | Kind::Variadic));
+    let mut current_positional = positionals.next();
+    let runs_program = runs_program(arguments);
+    let mut positional_seen = false;
+
+    let mut i = 0;
+    while i < tokens.len() && i <= index {
+        if ends_own_options(&tokens[i], runs_program && positional_seen) {
+            if i == index {
+                return None;
+            }
+            i += 1;
+            continue;
+        }
+        match named_argument(arguments, &tokens[i], runs_program && positional_seen) {
+            Ok(Some(named)) if named.value != Type::Switch => {
+                if i + 1 == index {
+                    return Some(named);
+                }
+                if i == index {
+                    return None;
+                }
+                i += 1;
+            }
+            // The name of an argument is not the value of one
+            Ok(Some(_)) | Err(_) if i == index => return None,
+            Ok(Some(_)) | Err(_) => {}
+            Ok(None) if i == index => return current_positional,
+            Ok(None) => {
+                positional_seen = true;
+                if current_positional.is_some_and(|a| a.kind != Kind::Variadic) {
+                    current_positional = positionals.next();
+                }
+            }
+        }
+        i += 1;
+    }
+
+    current_positional
+}
+
+/// Indexes of the `tokens` that are positional values, following the same rules as `bind`
+pub(crate) fn positionals(arguments: &[Argument], tokens: &[Token]) -> Vec<usize> {
+    let runs_program = runs_program(arguments);
+    let mut indexes = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let only_long = runs_program && !indexes.is_empty();
+        if ends_own_options(&tokens[i], only_long) {
+            i += 1;
+            continue;
+        }
+        match named_argument(arguments, &tokens[i], only_long) {
+            Ok(Some(named)) if named.value != Type::Switch => i += 1,
+            Ok(Some(_)) | Err(_) => {}
+            Ok(None) => indexes.push(i),
+        }
+        i += 1;
+    }
+
+    indexes
+}
+
+/// Whether the action takes a program, whose own options come after it
+fn runs_program(arguments: &[Argument]) -> bool {
+    arguments.iter().any(|a| a.value == Type::Executable)
+}
+
+/// Whether the token is the `--` ending the named arguments of the action, and not a value given to a program
+fn ends_own_options(token: &Token, in_program: bool) -> bool {
+    token.ends_options && !in_program
+}
+
+/// The named argument the token is the name of: `---<name>`, or `--<name>` unless `only_long`
+pub(crate) fn named_argument<'a>(
+    arguments: &'a [Argument],
+    token: &Token,
+    only_long: bool,
+) -> Result<Option<&'a Argument>, ParseError> {
+    if token.literal {
+        return Ok(None);
+    }
+
+    let name = if let Some(name) = token.value.strip_prefix("---") {
+        name
+    } else if let Some(name) = token.value.strip_prefix("--").filter(|_| !only_long) {
+        name
+    } else {
+        return Ok(None);
+    };
+
+    let found = arguments
+        .iter()
+        .find(|a| a.kind == Kind::Named && a.name.eq_ignore_ascii_case(name));
+    match found {
+        Some(argument) => Ok(Some(argument)),
+        // An unknown `--flag` may belong to the program given to `Run`, but `---` is always ours
+        None if token.value.starts_with("---") => Err(ParseError {
+            message: format!("Unknown option `{}`", token.value),
+            span: token.span.clone(),
+        }),
+        None => Ok(None),
+    }
+}
+
+fn check_type(argument: &Argument, token: &Token) -> Result<(), ParseError> {
+    match argument.value {
+        Type::Number if token.value.parse::<usize>().is_err() => Err(ParseError {
+            message: format!(
+                "`{}` expects a number, got `{}`",
+                argument.label(),
+                token.value
+            ),
+            span: token.span.clone(),
+        }),
+        Type::Assignment if split_assignment(&token.value).is_none() => Err(ParseError {
+            message: format!(
+                "`{}` expects a `KEY=VALUE`, got `{}`",
+                argument.label(),
+                token.value
+            ),
+            span: token.span.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// The name and value of an environment variable written as `KEY=VALUE`, like in a shell
+pub(crate) fn split_assignment(value: &str) -> Option<(&str, &str)> {
+    let (key, value) = value.split_once('=')?;
+    let mut chars = key.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    valid.then_some((key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::action::token::tokenize;
+
+    const PROGRAM: &[Argument] = &[
+        Argument::named("cwd", Type::Directory, ""),
+        Argument::named("yes", Type::Switch, ""),
+        Argument::positional("command", Type::Executable, "").required(),
+        Argument::variadic("args", Type::Text, ""),
+    ];
+    const FILES: &[Argument] = &[
+        Argument::variadic("paths", Type::Path, ""),
+        Argument::named("line", Type::Number, ""),
+        Argument::named("yes", Type::Switch, ""),
+    ];
+    const SINGLE: &[Argument] = &[Argument::positional("amount", Type::Text, "").default("+1")];
+
+    fn tokens(command: &str) -> Vec<Token> {
+        tokenize(command, &BTreeMap::new()).unwrap()
+    }
+
+    fn error(arguments: &[Argument], command: &str) -> String {
+        bind(arguments, &tokens(command)).unwrap_err().message
+    }
+
+    #[test]
+    fn bind_to_a_program() {
+        let values = bind(PROGRAM, &tokens("---cwd /x --yes cargo --cwd y -- z")).unwrap();
+        assert_eq!(values.get("cwd").as_deref(), Some("/x"));
+        assert!(values.is_set("yes"));
+        assert_eq!(values.get("command").as_deref(), Some("cargo"));
+        assert_eq!(values.get_all("args"), ["--cwd", "y", "--", "z"]);
+
+        // `---` is always ours
+        let values = bind(PROGRAM, &tokens("cargo test ---cwd /x")).unwrap();
+        assert_eq!(values.get("cwd").as_deref(), Some("/x"));
+        assert_eq!(values.get_all("args"), ["test"]);
+
+        let values = bind(PROGRAM, &tokens("-- --yes")).unwrap();
+        assert_eq!(values.get("command").as_deref(), Some("--yes"));
+        assert!(!values.is_set("yes"));
+    }
+
+    #[test]
+    fn bind_without_a_program() {
+        let values = bind(FILES, &tokens("a --line 3 b --yes")).unwrap();
+        assert_eq!(values.get_all("paths"), ["a", "b"]);
+        assert_eq!(values.get_number("line"), Some(3));
+        assert!(values.is_set("yes"));
+        assert_eq!(
+            values.get_all_literal("paths"),
+            [("a".to_string(), false), ("b".to_string(), false)]
+        );
+
+        let values = bind(FILES, &tokens("a -- --line")).unwrap();
+        assert_eq!(
+            values.get_all_literal("paths"),
+            [("a".to_string(), false), ("--line".to_string(), true)]
+        );
+    }
+
+    #[test]
+    fn defaults() {
+        let values = bind(SINGLE, &[]).unwrap();
+        assert_eq!(values.get("amount").as_deref(), Some("+1"));
+        assert!(!values.is_set("amount"));
+
+        let values = bind(SINGLE, &tokens("-2")).unwrap();
+        assert_eq!(values.get("amount").as_deref(), Some("-2"));
+        assert!(values.is_set("amount"));
+    }
+
+    #[test]
+    fn bind_errors() {
+        assert_eq!(
+            error(PROGRAM, "cargo ---unknown"),
+            "Unknown option `---unknown`"
+        );
+        assert_eq!(error(PROGRAM, "---cwd"), "`---cwd` expects a DIR");
+        assert_eq!(
+            error(FILES, "a --line x"),
+            "`---line` expects a number, got `x`"
+        );
+        assert_eq!(error(SINGLE, "+1 +2"), "Unexpected argument `+2`");
+    }
+
+    #[test]
+    fn argument_of_each_word() {
+        let name = |arguments, command: &str, index| {
+            argument_of(arguments, &tokens(command), index).map(|a| a.name)
+        };
+        let command = "---cwd /x cargo --cwd";
+        assert_eq!(name(PROGRAM, command, 0), None);
+        assert_eq!(name(PROGRAM, command, 1), Some("cwd"));
+        assert_eq!(name(PROGRAM, command, 2), Some("command"));
+        assert_eq!(name(PROGRAM, command, 3), Some("args"));
+        assert_eq!(name(PROGRAM, command, 4), Some("args"));
+        assert_eq!(name(PROGRAM, "", 0), Some("command"));
+        assert_eq!(name(PROGRAM, "--cwd", 1), Some("cwd"));
+
+        assert_eq!(name(FILES, "a --line", 2), Some("line"));
+        assert_eq!(name(FILES, "a --line 3", 3), Some("paths"));
+        assert_eq!(name(FILES, "a --", 1), None);
+        assert_eq!(name(FILES, "a -- --line", 2), Some("paths"));
+    }
+
+    #[test]
+    fn positional_words() {
+        assert_eq!(
+            positionals(PROGRAM, &tokens("--cwd /x cargo --cwd y -- z")),
+            [2, 3, 4, 5, 6]
+        );
+        assert_eq!(
+            positionals(FILES, &tokens("a --line 3 b --yes -- --line")),
+            [0, 3, 6]
+        );
+        assert!(positionals(FILES, &tokens("--yes")).is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+/// Whether `input` is an abbreviation of `name`, ignoring the case.
+///
+/// It is either the start of `name` (`ru` for `Run`), or the start of each of its words,
+/// separated by `-` or `_` (`det-e` for `DetachEveryone`).
+pub(crate) fn abbreviates(input: &str, name: &str) -> bool {
+    let input = input.to_lowercase();
+    if name.to_lowercase().starts_with(&input) {
+        return true;
+    }
+
+    let segments: Vec<_> = input.split(['-', '_']).collect();
+    let words = words(name);
+    segments.len() > 1
+        && segments.len() <= words.len()
+        && segments
+            .iter()
+            .zip(&words)
+            .all(|(segment, word)| word.starts_with(segment))
+}
+
+/// Lowercase words of a name written in `CamelCase`, `kebab-case` or `snake_case`
+fn words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut previous_is_lowercase = false;
+    for c in name.chars() {
+        if c == '-' || c == '_' {
+            previous_is_lowercase = false;
+            words.push(String::new());
+            continue;
+        }
+
+        match words.last_mut() {
+            Some(word) if !(c.is_uppercase() && previous_is_lowercase) => {
+                word.extend(c.to_lowercase())
+            }
+            _ => words.push(c.to_lowercase().collect()),
+        }
+        previous_is_lowercase = c.is_lowercase();
+    }
+
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+/// How many typos separate `input` from the start of `candidate`, ignoring the case.
+///
+/// A typo is an insertion, a deletion, a substitution or the transposition of 2 adjacent characters.
+/// Since only the start of `candidate` is compared, `newpan` is 0 typo away from `NewPane` and `detch` is 1 away from `DetachMe`.
+pub(crate) fn prefix_distance(input: &str, candidate: &str) -> usize {
+    let input: Vec<_> = input.to_lowercase().chars().collect();
+    let candidate: Vec<_> = candidate.to_lowercase().chars().collect();
+
+    // distances[i][j]: typos between the first `i` chars of `input` and the first `j` of `candidate`
+    let mut distances = vec![vec![0; candidate.len() + 1]; input.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=input.len() {
+        for j in 1..=candidate.len() {
+            let substitution = usize::from(input[i - 1] != candidate[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + substitution);
+            if i > 1
+                && j > 1
+                && input[i - 1] == candidate[j - 2]
+                && input[i - 2] == candidate[j - 1]
+            {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[input.len()]
+        .iter()
+        .copied()
+        .min()
+        .unwrap_or(input.len())
+}
+
+/// The most typos `input` can have and still be recognized: 1 every 3 characters.
+/// Shorter inputs must be exact, or every action would be 1 typo away from them.
+pub(crate) fn tolerance(input: &str) -> usize {
+    input.chars().count() / 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abbreviations() {
+        assert!(abbreviates("ru", "Run"));
+        assert!(abbreviates("RUN", "Run"));
+        assert!(abbreviates("det-e", "DetachEveryone"));
+        assert!(abbreviates("d_e", "DetachEveryone"));
+        assert!(abbreviates("new-p", "new_pane"));
+        assert!(abbreviates("", "Run"));
+        assert!(!abbreviates("det-m", "DetachEveryone"));
+        assert!(!abbreviates("n-p-x", "NewPane"));
+        assert!(!abbreviates("pane", "NewPane"));
+    }
+
+    #[test]
+    fn words_of_a_name() {
+        assert_eq!(words("DetachEveryone"), ["detach", "everyone"]);
+        assert_eq!(words("kill-job"), ["kill", "job"]);
+        assert_eq!(words("Kill_Job"), ["kill", "job"]);
+        assert_eq!(words("HTTPServer"), ["httpserver"]);
+    }
+
+    #[test]
+    fn distance_to_the_start_of_a_name() {
+        assert_eq!(prefix_distance("newpan", "NewPane"), 0);
+        assert_eq!(prefix_distance("NEWPANE", "NewPane"), 0);
+        assert_eq!(prefix_distance("detch", "DetachMe"), 1);
+        assert_eq!(prefix_distance("nwepane", "NewPane"), 1);
+        assert_eq!(prefix_distance("neewpane", "NewPane"), 1);
+        assert_eq!(prefix_distance("rnu", "Run"), 1);
+        assert_eq!(prefix_distance("xyz", "Run"), 3);
+        assert_eq!(prefix_distance("", "Run"), 0);
+        assert_eq!(prefix_distance("run", ""), 3);
+    }
+
+    #[test]
+    fn tolerance_grows_with_the_input() {
+        assert_eq!(tolerance("ru"), 0);
+        assert_eq!(tolerance("run"), 1);
+        assert_eq!(tolerance("newpan"), 2);
+        assert_eq!(tolerance("éèà"), 1);
+    }
+}
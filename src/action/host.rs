@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+
+use super::argument::split_assignment;
+
+/// Where Zellij mounts the folder it was started in
+pub(crate) const HOST_FOLDER: &str = "/host";
+/// Where Zellij keeps the plugin’s data between sessions
+pub(crate) const CACHE_FOLDER: &str = "/cache";
+
+/// Whether the path contains a glob pattern: `*`, `?` or `[...]`
+pub(crate) fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Where the plugin can read a `path` of the host, if it is in the folder Zellij was started in
+pub(crate) fn to_plugin(path: &Path, initial_cwd: &Path) -> Option<PathBuf> {
+    path.strip_prefix(initial_cwd)
+        .ok()
+        .map(|relative| Path::new(HOST_FOLDER).join(relative))
+}
+
+/// The path on the host of a `path` read by the plugin
+pub(crate) fn to_host(path: &Path, initial_cwd: &Path) -> PathBuf {
+    match path.strip_prefix(HOST_FOLDER) {
+        Ok(relative) => initial_cwd.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// The files matching the glob `pattern`, relative to `cwd`
+pub(crate) fn expand_glob(
+    pattern: &str,
+    cwd: &Path,
+    initial_cwd: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    // Only the pattern is a glob, the folders it is relative to are escaped
+    let (base, relative) = if Path::new(pattern).is_absolute() {
+        (Path::new("/"), pattern.trim_start_matches('/'))
+    } else {
+        (cwd, pattern)
+    };
+    let prefix = literal_prefix(relative);
+    let plugin_pattern = match to_plugin(&base.join(prefix), initial_cwd) {
+        Some(folder) => format!(
+            "{}/{}",
+            Pattern::escape(&folder.to_string_lossy()).trim_end_matches('/'),
+            &relative[prefix.len()..]
+        ),
+        None => {
+            return Err(format!(
+                "Cannot search `{pattern}`: only `{}` is readable",
+                initial_cwd.display()
+            ))
+        }
+    };
+
+    let matches = glob::glob(&plugin_pattern)
+        .map_err(|error| format!("Invalid pattern `{pattern}`: {}", error.msg))?;
+    let files: Vec<_> = matches
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .map(|path| to_host(&path, initial_cwd))
+        .collect();
+
+    if files.is_empty() {
+        Err(format!("No file matches `{pattern}`"))
+    } else {
+        Ok(files)
+    }
+}
+
+/// The variables of a `.env` file, relative to `cwd`: one `KEY=VALUE` per line, `#` starts a comment.
+/// Like in a shell, the value can be quoted and the line can start with `export`.
+pub(crate) fn read_env_file(
+    path: &str,
+    cwd: &Path,
+    initial_cwd: &Path,
+) -> Result<Vec<(String, String)>, String> {
+    let Some(file) = to_plugin(&cwd.join(path), initial_cwd) else {
+        return Err(format!(
+            "Cannot read `{path}`: only `{}` is readable",
+            initial_cwd.display()
+        ));
+    };
+    let content =
+        std::fs::read_to_string(file).map_err(|error| format!("Cannot read `{path}`: {error}"))?;
+
+    let mut variables = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let Some((key, value)) = split_assignment(line) else {
+            return Err(format!(
+                "Line {} of `{path}` is not a `KEY=VALUE`",
+                number + 1
+            ));
+        };
+        variables.push((key.to_string(), env_value(value).to_string()));
+    }
+
+    Ok(variables)
+}
+
+/// The value of a `KEY=VALUE` line of a `.env` file: quotes are removed,
+/// and outside of them a ` #` starts a comment, like with dotenv
+fn env_value(value: &str) -> &str {
+    let value = value.trim();
+    let quoted = ['"', '\''].into_iter().find_map(|quote| {
+        let rest = value.strip_prefix(quote)?;
+        let end = rest.find(quote)?;
+        let after = rest[end + 1..].trim_start();
+        (after.is_empty() || after.starts_with('#')).then_some(&rest[..end])
+    });
+    if let Some(value) = quoted {
+        return value;
+    }
+
+    let end = value
+        .char_indices()
+        .find(|&(i, c)| c == '#' && (i == 0 || value[..i].ends_with(char::is_whitespace)))
+        .map_or(value.len(), |(i, _)| i);
+    value[..end].trim_end()
+}
+
+/// The folders at the start of the `pattern` that contain no glob: `src/action/` in `src/action/*.rs`
+fn literal_prefix(pattern: &str) -> &str {
+    let first_glob = pattern.find(['*', '?', '[']).unwrap_or(pattern.len());
+    match pattern[..first_glob].rfind('/') {
+        Some(slash) => &pattern[..=slash],
+        None => "",
+    }
+}
+
+/// The paths starting with `prefix`, relative to `cwd`. Folders end with a `/` so their content can be completed next.
+/// Hidden files are only proposed if the name being completed starts with a `.`.
+pub(crate) fn complete_path(
+    prefix: &str,
+    cwd: &Path,
+    initial_cwd: &Path,
+    directories_only: bool,
+) -> Vec<String> {
+    let (folder, start) = match prefix.rsplit_once('/') {
+        Some((folder, start)) => (format!("{folder}/"), start),
+        None => (String::new(), prefix),
+    };
+    let Some(plugin_folder) = to_plugin(&cwd.join(&folder), initial_cwd) else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(plugin_folder) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.path().is_dir();
+            let visible = !name.starts_with('.') || start.starts_with('.');
+            (name.starts_with(start) && visible && (is_dir || !directories_only)).then(|| {
+                if is_dir {
+                    format!("{folder}{name}/")
+                } else {
+                    format!("{folder}{name}")
+                }
+            })
+        })
+        .collect();
+    candidates.sort();
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_values() {
+        let cases = [
+            ("value", "value"),
+            ("  value  ", "value"),
+            ("value # comment", "value"),
+            ("value\t# comment", "value"),
+            ("a#b", "a#b"),
+            ("# comment", ""),
+            ("", ""),
+            ("'a # b'", "a # b"),
+            ("\"a # b\" # comment", "a # b"),
+            ("' spaced '", " spaced "),
+            ("\"a\"b", "\"a\"b"),
+            ("'unterminated", "'unterminated"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(env_value(value), expected, "{value:?}");
+        }
+    }
+}
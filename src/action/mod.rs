@@ -1,9 +1,27 @@
+pub(crate) mod argument;
+mod fuzzy;
+pub(crate) mod host;
+pub(crate) mod sequence;
+pub(crate) mod token;
+
+use std::collections::BTreeMap;
+use std::ops::Range;
 use std::path::PathBuf;
 
 use strum::{EnumMessage, EnumProperty, IntoEnumIterator};
 use strum_macros::{EnumIter, EnumMessage, EnumProperty};
 
-use zellij_tile::prelude::{CommandToRun, FileToOpen};
+use zellij_tile::prelude::{CommandToRun, Direction, FileToOpen};
+
+use crate::focus::PaneRef;
+use crate::history::History;
+
+use argument::{
+    argument_of, bind, named_argument, positionals, split_assignment, Argument, Type, Values,
+};
+use fuzzy::{abbreviates, prefix_distance, tolerance};
+use sequence::Sequence;
+use token::{quote, tokenize_from, Checkpoint, Token};
 
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub(crate) enum Selection {
@@ -36,14 +54,21 @@ pub(crate) enum Interface {
 // 2) "Pipe": Only available on the "pipe" interface
 // 3) "All": Available "all" the interfaces
 // TODO: Is it possible to not repeat the `serialize` with all "-" and "_" combinations?
-#[derive(Debug, Default, Clone, EnumIter, EnumMessage, EnumProperty)]
+// Short names are not needed: any abbreviation of a single action is recognized (`ru` for `Run`, `det-e` for `DetachEveryone`)
+#[derive(Debug, Clone, EnumIter, EnumMessage, EnumProperty)]
 pub(crate) enum ActionList {
     /*
         Technical actions
     */
     /// No action have been recognized
-    #[default]
-    Unknown,
+    Unknown {
+        /// What was typed instead of an action name, empty when nothing was typed yet
+        name: String,
+        /// Where `name` is in the prompt
+        span: Range<usize>,
+        /// Names of the actions close to `name`, the closest first
+        suggestions: Vec<String>,
+    },
     /// This recognized `action` is not available through the requested `calling_interface`
     //
     // This is an equivalent of custom Result::Error.
@@ -52,6 +77,14 @@ pub(crate) enum ActionList {
         action: Box<ActionList>,
         calling_interface: Interface,
     },
+    /// The command could not be read (Ex: a quote is not closed)
+    Invalid { error: ParseError },
+    /// The name abbreviates several actions
+    Ambiguous {
+        name: String,
+        /// Canonical names of the actions it abbreviates
+        candidates: Vec<String>,
+    },
     /// Show the list of all commands
     #[strum(
         props(Interface = "All"),
@@ -62,7 +95,11 @@ pub(crate) enum ActionList {
         serialize = "h",
         serialize = "?"
     )]
-    HelpAll { selection: Selection },
+    HelpAll {
+        selection: Selection,
+        /// The user’s aliases, documented after the actions
+        aliases: BTreeMap<String, String>,
+    },
     /// Show the list of commands available through the `pane` interface
     #[strum(
         props(Interface = "All"),
@@ -83,15 +120,19 @@ pub(crate) enum ActionList {
     /*
         Zellij actions (sorted alphabetically)
     */
-    // /// Clear the last focused pane’s scroll buffer
-    // #[strum(
-    //     serialize = "ClearScreen",
-    //     serialize = "Clear-Screen",
-    //     serialize = "Clear_Screen",
-    //     serialize = "Clear",
-    //     serialize = "cl"
-    // )]
-    // ClearScreen,
+    /// Clear the last focused pane’s scroll buffer
+    #[strum(
+        props(Interface = "All"),
+        serialize = "ClearScreen",
+        serialize = "Clear-Screen",
+        serialize = "Clear_Screen",
+        serialize = "Clear",
+        serialize = "cl"
+    )]
+    ClearScreen {
+        /// Terminal pane, `None` for the last focused one
+        pane: Option<u32>,
+    },
     // /// Close the focused pane
     // #[strum(
     //     serialize = "Close",
@@ -110,36 +151,42 @@ pub(crate) enum ActionList {
     //     serialize = "Exit_Pane"
     // )]
     // CloseFocus,
-    // /// Close the focused tab
-    // #[strum(
-    //     serialize = "CloseFocusTab",
-    //     serialize = "Close-Focus-Tab",
-    //     serialize = "Close_Focus_Tab",
-    //     serialize = "CloseTab",
-    //     serialize = "Close-Tab",
-    //     serialize = "Close_Tab"
-    // )]
-    // CloseFocusTab,
-    // /// Close the specified plugin pane
-    // #[strum(
-    //     serialize = "ClosePluginPane",
-    //     serialize = "Close-Plugin-Pane",
-    //     serialize = "Close_Plugin_Pane",
-    //     serialize = "ClosePlugin",
-    //     serialize = "Close-Plugin",
-    //     serialize = "Close_Plugin"
-    // )]
-    // ClosePluginPane { id: Option<u32> },
-    // /// Close the specified terminal pane
-    // #[strum(
-    //     serialize = "CloseTerminalPane",
-    //     serialize = "Close-Terminal-Pane",
-    //     serialize = "Close_Terminal_Pane",
-    //     serialize = "CloseTerminal",
-    //     serialize = "Close-Terminal",
-    //     serialize = "Close_Terminal"
-    // )]
-    // CloseTerminalPane { id: Option<u32> },
+    /// Close a tab, the focused one by default
+    #[strum(
+        props(Interface = "All"),
+        serialize = "CloseTab",
+        serialize = "Close-Tab",
+        serialize = "Close_Tab",
+        serialize = "CloseFocusTab",
+        serialize = "Close-Focus-Tab",
+        serialize = "Close_Focus_Tab"
+    )]
+    CloseTab {
+        /// From 0, `None` for the focused tab
+        position: Option<usize>,
+    },
+    /// Close a plugin pane, found by its id, its title or its plugin name
+    #[strum(
+        props(Interface = "All"),
+        serialize = "ClosePluginPane",
+        serialize = "Close-Plugin-Pane",
+        serialize = "Close_Plugin_Pane",
+        serialize = "ClosePlugin",
+        serialize = "Close-Plugin",
+        serialize = "Close_Plugin"
+    )]
+    ClosePluginPane { id: u32 },
+    /// Close a terminal pane, found by its id, its title or its command
+    #[strum(
+        props(Interface = "All"),
+        serialize = "CloseTerminalPane",
+        serialize = "Close-Terminal-Pane",
+        serialize = "Close_Terminal_Pane",
+        serialize = "CloseTerminal",
+        serialize = "Close-Terminal",
+        serialize = "Close_Terminal"
+    )]
+    CloseTerminalPane { id: u32 },
     /// Detach everyone from the current session
     #[strum(
         props(Interface = "All"),
@@ -164,13 +211,162 @@ pub(crate) enum ActionList {
         serialize = "Detach_Others"
     )]
     DetachOthers,
-    // /// Edit a pane scrollback
-    // EditScrollback,
-    /// Edit a file in a new edit pane
+    /// Edit the last focused pane’s scrollback
+    #[strum(
+        props(Interface = "All"),
+        serialize = "EditScrollback",
+        serialize = "Edit-Scrollback",
+        serialize = "Edit_Scrollback"
+    )]
+    EditScrollback {
+        /// Terminal pane, `None` for the last focused one
+        pane: Option<u32>,
+    },
+    /// Run a command in the background, its output is shown by `Jobs`
+    #[strum(
+        props(Interface = "Pane"), // The result comes back to the plugin pane that started it
+    )]
+    Job {
+        command: CommandToRun,
+        /// Variables added to the environment of the command, those of the `env_file` first
+        env: BTreeMap<String, String>,
+        env_file: Option<PathBuf>,
+    },
+    /// Show the jobs and the output of the selected one
+    #[strum(props(Interface = "Pane"))]
+    Jobs,
+    /// Forget the jobs that are done
+    #[strum(
+        props(Interface = "Pane"),
+        serialize = "ClearJobs",
+        serialize = "Clear-Jobs",
+        serialize = "Clear_Jobs"
+    )]
+    ClearJobs,
+    /// Kill a job that is still running
+    #[strum(
+        props(Interface = "Pane"),
+        serialize = "KillJob",
+        serialize = "Kill-Job",
+        serialize = "Kill_Job"
+    )]
+    KillJob { id: Option<usize> },
+    /// Edit files in new edit panes
     #[strum(
         props(Interface = "Pane"), // The cli does not know who wrote the command or who pressed `Enter` --> The edit will be done by every users
     )]
-    Edit(FileToOpen),
+    Edit {
+        /// The files to open, once the globs are expanded
+        files: Vec<FileToOpen>,
+        // TODO: Zellij’s `FileToOpen` has no column, so it is only shown for now
+        column: Option<usize>,
+        /// Open the files stacked in a new tab instead of the current one
+        new_tab: bool,
+        /// Open more than `MAX_FILES` files without asking first
+        confirmed: bool,
+    },
+    /// Put a floating pane back in the layout
+    #[strum(
+        props(Interface = "All"),
+        serialize = "Embed",
+        serialize = "EmbedPane",
+        serialize = "Embed-Pane",
+        serialize = "Embed_Pane"
+    )]
+    Embed {
+        /// `None` for the last focused pane
+        pane: Option<PaneRef>,
+    },
+    /// Make a pane float above the layout
+    #[strum(
+        props(Interface = "All"),
+        serialize = "Float",
+        serialize = "FloatPane",
+        serialize = "Float-Pane",
+        serialize = "Float_Pane"
+    )]
+    Float {
+        /// `None` for the last focused pane
+        pane: Option<PaneRef>,
+    },
+    /// Jump to a pane of any tab, the list is filtered by what is written after the action
+    #[strum(
+        props(Interface = "Pane"), // The list is browsed in the plugin pane
+        serialize = "Focus",
+        serialize = "FocusPane",
+        serialize = "Focus-Pane",
+        serialize = "Focus_Pane"
+    )]
+    Focus {
+        filter: Vec<String>,
+        /// The panes matching the `filter`
+        panes: Vec<Pane>,
+        /// Index in `panes` of the one focused on `Enter`
+        selected: usize,
+    },
+    /// Show or hide the frames of the panes
+    #[strum(
+        props(Interface = "All"),
+        serialize = "Frames",
+        serialize = "PaneFrames",
+        serialize = "Pane-Frames",
+        serialize = "Pane_Frames",
+        serialize = "TogglePaneFrames",
+        serialize = "Toggle-Pane-Frames",
+        serialize = "Toggle_Pane_Frames"
+    )]
+    Frames {
+        /// `None` to toggle them
+        shown: Option<bool>,
+    },
+    /// Toggle the fullscreen of a pane
+    #[strum(
+        props(Interface = "All"),
+        serialize = "Fullscreen",
+        serialize = "ToggleFullscreen",
+        serialize = "Toggle-Fullscreen",
+        serialize = "Toggle_Fullscreen"
+    )]
+    Fullscreen {
+        /// `None` for the last focused pane
+        pane: Option<PaneRef>,
+    },
+    /// Focus a tab, found by its name or its position
+    #[strum(
+        props(Interface = "All"),
+        serialize = "GoToTab",
+        serialize = "Go-To-Tab",
+        serialize = "Go_To_Tab",
+        serialize = "Tab"
+    )]
+    GoToTab {
+        /// From 0
+        position: usize,
+    },
+    /// Run the commands of a macro defined in the configuration
+    #[strum(props(Interface = "All"))]
+    Macro { name: String, steps: Vec<String> },
+    /// Move a pane in the layout, or swap it with the next one
+    #[strum(
+        props(Interface = "All"),
+        serialize = "Move",
+        serialize = "MovePane",
+        serialize = "Move-Pane",
+        serialize = "Move_Pane"
+    )]
+    Move {
+        direction: Option<Direction>,
+        /// `None` for the last focused pane
+        pane: Option<PaneRef>,
+    },
+    /// Move the focused tab, to the left when `offset` is negative
+    #[strum(
+        props(Interface = "All"),
+        serialize = "MoveTab",
+        serialize = "Move-Tab",
+        serialize = "Move_Tab"
+    )]
+    MoveTab { offset: isize },
     /// Open a new pane in the current tab
     #[strum(
         props(Interface = "Pane"), // The cli does not know who wrote the command or who pressed `Enter` --> The edit will be done by every users
@@ -180,14 +376,443 @@ pub(crate) enum ActionList {
         serialize = "np"
     )]
     NewPane { path: String },
+    /// Open a new tab
+    #[strum(
+        props(Interface = "All"),
+        serialize = "NewTab",
+        serialize = "New-Tab",
+        serialize = "New_Tab"
+    )]
+    NewTab {
+        name: Option<String>,
+        /// Directory the terminal of the new tab starts in
+        cwd: Option<PathBuf>,
+    },
+    /// Rename a tab, the focused one by default
+    #[strum(
+        props(Interface = "All"),
+        serialize = "RenameTab",
+        serialize = "Rename-Tab",
+        serialize = "Rename_Tab"
+    )]
+    RenameTab {
+        name: String,
+        /// From 0, `None` for the focused tab
+        position: Option<usize>,
+    },
+    /// Grow or shrink a pane, on one side or all of them
+    #[strum(
+        props(Interface = "All"),
+        serialize = "Resize",
+        serialize = "ResizePane",
+        serialize = "Resize-Pane",
+        serialize = "Resize_Pane"
+    )]
+    Resize {
+        /// `None` for every side
+        direction: Option<Direction>,
+        /// How many times Zellij resizes the pane, it shrinks when negative
+        amount: isize,
+        /// `None` for the last focused pane
+        pane: Option<PaneRef>,
+    },
     #[strum(
         props(Interface = "Pane"), // The cli does not know who wrote the command or who pressed `Enter` --> The edit will be done by every users
     )]
     /// Run a command in a new edit pane
-    Run(CommandToRun),
+    Run {
+        command: CommandToRun,
+        /// Variables added to the environment of the command, those of the `env_file` first
+        env: BTreeMap<String, String>,
+        env_file: Option<PathBuf>,
+    },
+}
+
+const HELP_ARGUMENTS: &[Argument] = &[Argument::named(
+    "expand",
+    Type::Switch,
+    "Show the details of every action",
+)];
+const CLOSE_TAB_ARGUMENTS: &[Argument] = &[Argument::positional(
+    "tab",
+    Type::Tab,
+    "The tab to close, the focused one by default",
+)];
+const TARGET_PANE_ARGUMENTS: &[Argument] = &[Argument::named(
+    "pane",
+    Type::Pane,
+    "The id, title or command of the terminal pane, the last focused one by default",
+)];
+const CLOSE_PANE_ARGUMENTS: &[Argument] = &[Argument::positional(
+    "pane",
+    Type::Pane,
+    "The id, title or command of the pane to close",
+)
+.required()];
+/// The pane an action applies to
+const PANE_ARGUMENT: Argument = Argument::named(
+    "pane",
+    Type::Pane,
+    "The id, title or command of the pane, the last focused one by default",
+);
+const DIRECTION_ARGUMENT: Argument =
+    Argument::positional("direction", Type::Text, "`left`, `right`, `up` or `down`");
+const EDIT_ARGUMENTS: &[Argument] = &[
+    Argument::variadic(
+        "paths",
+        Type::Path,
+        "The files to edit, globs are searched in the folder Zellij started in",
+    )
+    .required(),
+    Argument::named("line", Type::Number, "Line where the cursor is placed"),
+    Argument::named("column", Type::Number, "Column where the cursor is placed"),
+    Argument::named(
+        "cwd",
+        Type::Directory,
+        "Directory the paths are relative to",
+    ),
+    Argument::named("tab", Type::Switch, "Open the files stacked in a new tab"),
+    Argument::named("yes", Type::Switch, "Open many files without asking first"),
+];
+const FRAMES_ARGUMENTS: &[Argument] = &[Argument::positional(
+    "state",
+    Type::Text,
+    "`on` or `off`, toggled by default",
+)];
+const FOCUS_ARGUMENTS: &[Argument] = &[Argument::variadic(
+    "filter",
+    Type::Text,
+    "Words found in the tab, the title or the command of the pane",
+)];
+const GO_TO_TAB_ARGUMENTS: &[Argument] =
+    &[Argument::positional("tab", Type::Tab, "The tab to focus").required()];
+const KILL_JOB_ARGUMENTS: &[Argument] = &[Argument::positional(
+    "id",
+    Type::Number,
+    "The job to kill, the last one running by default",
+)];
+const MACRO_ARGUMENTS: &[Argument] =
+    &[
+        Argument::positional("name", Type::Text, "Name of the macro in the configuration")
+            .required(),
+    ];
+const MOVE_ARGUMENTS: &[Argument] = &[DIRECTION_ARGUMENT, PANE_ARGUMENT];
+const MOVE_TAB_ARGUMENTS: &[Argument] = &[Argument::positional(
+    "to",
+    Type::Tab,
+    "`left`, `right`, or the tab whose place the focused tab takes",
+)
+.required()];
+const NEW_PANE_ARGUMENTS: &[Argument] = &[Argument::positional(
+    "path",
+    Type::Directory,
+    "Directory the new terminal starts in",
+)];
+const NEW_TAB_ARGUMENTS: &[Argument] = &[
+    Argument::positional("name", Type::Text, "Name of the new tab"),
+    Argument::named(
+        "cwd",
+        Type::Directory,
+        "Directory the terminal of the new tab starts in",
+    ),
+];
+const RENAME_TAB_ARGUMENTS: &[Argument] = &[
+    Argument::positional("name", Type::Text, "The new name of the tab").required(),
+    Argument::named(
+        "tab",
+        Type::Tab,
+        "The tab to rename, the focused one by default",
+    ),
+];
+const RESIZE_ARGUMENTS: &[Argument] = &[
+    DIRECTION_ARGUMENT,
+    Argument::positional("amount", Type::Text, "`+N` to grow, `-N` to shrink").default("+1"),
+    PANE_ARGUMENT,
+];
+const RUN_ARGUMENTS: &[Argument] = &[
+    Argument::named(
+        "env",
+        Type::Assignment,
+        "Variable given to the program, also written `KEY=VALUE` before it",
+    ),
+    Argument::named(
+        "env-file",
+        Type::Path,
+        "File of `KEY=VALUE` lines given to the program",
+    ),
+    Argument::positional("command", Type::Executable, "The program to run").required(),
+    Argument::variadic("args", Type::Text, "Arguments given to the program"),
+    Argument::named("cwd", Type::Directory, "Directory the program runs in"),
+];
+
+/// How many actions are proposed when the name is not recognized
+const MAX_SUGGESTIONS: usize = 5;
+
+/// How many files `Edit` opens before asking for a confirmation
+pub(crate) const MAX_FILES: usize = 10;
+
+/// Indexes of the words before the program that are variables given to it, as in a shell: `Run RUST_LOG=debug cargo run`.
+/// The named arguments can be mixed with them, `literal` words are always the program or its arguments.
+fn leading_assignments(arguments: &[Token]) -> Vec<usize> {
+    let mut indexes = Vec::new();
+    let mut i = 0;
+    while let Some(token) = arguments.get(i) {
+        match named_argument(RUN_ARGUMENTS, token, false) {
+            // Its value is skipped too
+            Ok(Some(named)) => i += usize::from(named.value != Type::Switch),
+            Err(_) => {}
+            Ok(None) if !token.literal && split_assignment(&token.value).is_some() => {
+                indexes.push(i)
+            }
+            Ok(None) => break,
+        }
+        i += 1;
+    }
+
+    indexes
 }
 
-fn deserialize_action(action: &String, variant: impl EnumMessage) -> bool {
+/// Rewrite the `leading_assignments` with the `---env` named argument
+fn split_assignments(arguments: &[Token]) -> Vec<Token> {
+    let assignments = leading_assignments(arguments);
+    let mut tokens = Vec::with_capacity(arguments.len() + assignments.len());
+    for (i, token) in arguments.iter().enumerate() {
+        if assignments.contains(&i) {
+            tokens.push(Token {
+                value: String::from("---env"),
+                ..token.clone()
+            });
+        }
+        tokens.push(token.clone());
+    }
+
+    tokens
+}
+
+/// Read the locations of a single file written as separate words: `+line path` and `path line column`.
+/// They are rewritten with the `---line` and `---column` named arguments.
+/// The `path:line:column` of each file is read by `split_position`.
+fn split_location(arguments: &[Token]) -> Vec<Token> {
+    let indexes = positionals(EDIT_ARGUMENTS, arguments);
+    let is_number = |token: &Token| {
+        !token.literal && !token.value.is_empty() && token.value.chars().all(|c| c.is_ascii_digit())
+    };
+    let named = |name: &str, value: &str, token: &Token| {
+        [
+            Token {
+                value: format!("---{name}"),
+                literal: false,
+                ..token.clone()
+            },
+            Token {
+                value: value.to_string(),
+                ..token.clone()
+            },
+        ]
+    };
+
+    let mut tokens = arguments.to_vec();
+    match indexes[..] {
+        [first, _, ..]
+            if !tokens[first].literal
+                && tokens[first]
+                    .value
+                    .strip_prefix('+')
+                    .is_some_and(|line| line.parse::<usize>().is_ok()) =>
+        {
+            let token = tokens[first].clone();
+            tokens.splice(first..=first, named("line", &token.value[1..], &token));
+        }
+        [path, line, ref column @ ..]
+            if column.len() <= 1
+                && !is_number(&tokens[path])
+                && is_number(&tokens[line])
+                && column.iter().all(|&column| is_number(&tokens[column])) =>
+        {
+            // The last one first, so the index of the line is still right
+            if let [column] = column {
+                let token = tokens[*column].clone();
+                tokens.splice(*column..=*column, named("column", &token.value, &token));
+            }
+            let token = tokens[line].clone();
+            tokens.splice(line..=line, named("line", &token.value, &token));
+        }
+        _ => {}
+    }
+
+    tokens
+}
+
+/// Split the location written by compilers and grep at the end of a path: `path:line` or `path:line:column`
+fn split_position(path: &str) -> (&str, Option<usize>, Option<usize>) {
+    let mut rest = path.trim_end_matches(':');
+    let mut position = Vec::new();
+    while position.len() < 2 {
+        match rest.rsplit_once(':') {
+            Some((start, number)) if !start.is_empty() => match number.parse::<usize>() {
+                Ok(number) => {
+                    position.insert(0, number);
+                    rest = start;
+                }
+                Err(_) => break,
+            },
+            _ => break,
+        }
+    }
+
+    match position[..] {
+        [line] => (rest, Some(line), None),
+        [line, column] => (rest, Some(line), Some(column)),
+        _ => (rest, None, None),
+    }
+}
+
+/// The position of the tab written `query`: its position from 1, its name, or an abbreviation of it.
+/// A misspelled name is only corrected when `fuzzy`, never for the actions that close or change a tab.
+fn find_tab(tabs: &[String], query: &str, fuzzy: bool) -> Result<usize, String> {
+    let exact = tabs
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(query));
+    if let Some(position) = exact {
+        return Ok(position);
+    }
+    if let Ok(number) = query.parse::<usize>() {
+        return match number {
+            1.. if number <= tabs.len() => Ok(number - 1),
+            _ => Err(format!(
+                "There is no tab {number}, there are {}",
+                tabs.len()
+            )),
+        };
+    }
+
+    let matches: Vec<_> = (0..tabs.len())
+        .filter(|&position| abbreviates(query, &tabs[position]))
+        .collect();
+    match matches[..] {
+        [position] => return Ok(position),
+        [_, _, ..] => {
+            let candidates: Vec<_> = matches.iter().map(|&p| tabs[p].as_str()).collect();
+            return Err(format!(
+                "`{query}` could be the tabs: {}",
+                candidates.join(", ")
+            ));
+        }
+        [] => {}
+    }
+
+    let closest = (0..tabs.len())
+        .map(|position| (prefix_distance(query, &tabs[position]), position))
+        .filter(|(distance, _)| fuzzy && *distance <= tolerance(query))
+        .min();
+    match closest {
+        Some((_, position)) => Ok(position),
+        None => Err(format!(
+            "No tab named `{query}`, the tabs are: {}",
+            tabs.join(", ")
+        )),
+    }
+}
+
+/// The pane written `query`: its id, its title or its command, or an abbreviation of them.
+/// Only the plugin panes are searched when `plugin` is `Some(true)`, only the terminal panes when `Some(false)`.
+/// Among all the panes, an id is written `terminal_3` or `plugin_3`, like in Zellij’s cli.
+fn find_pane<'a>(panes: &'a [Pane], query: &str, plugin: Option<bool>) -> Result<&'a Pane, String> {
+    let panes: Vec<_> = panes
+        .iter()
+        .filter(|pane| plugin.is_none_or(|plugin| pane.plugin == plugin))
+        .collect();
+    let kind = match plugin {
+        Some(true) => "plugin pane",
+        Some(false) => "terminal pane",
+        None => "pane",
+    };
+
+    let by_id: fn(&str, &Pane) -> bool = |query, pane| {
+        query == pane.id.to_string() || query.eq_ignore_ascii_case(&pane.qualified_id())
+    };
+    let exactly: fn(&str, &Pane) -> bool =
+        |query, pane| pane.names().any(|name| name.eq_ignore_ascii_case(query));
+    let abbreviated: fn(&str, &Pane) -> bool =
+        |query, pane| pane.names().any(|name| abbreviates(query, name));
+    for matches in [by_id, exactly, abbreviated] {
+        let found: Vec<_> = panes
+            .iter()
+            .copied()
+            .filter(|pane| matches(query, pane))
+            .collect();
+        match found[..] {
+            [] => {}
+            [pane] => return Ok(pane),
+            _ => {
+                let candidates: Vec<_> = found
+                    .iter()
+                    .map(|pane| pane.describe(plugin.is_none()))
+                    .collect();
+                return Err(format!(
+                    "`{query}` could be several {kind}s, give the id of one: {}",
+                    candidates.join(", ")
+                ));
+            }
+        }
+    }
+
+    Err(format!("No {kind} is named `{query}`"))
+}
+
+/// How the target of an action is shown: `terminal_3`, or nothing for the last focused pane
+fn describe_target(pane: &Option<PaneRef>) -> String {
+    pane.map(|pane| {
+        let kind = if pane.plugin { "plugin" } else { "terminal" };
+        format!("{kind}_{}", pane.id)
+    })
+    .unwrap_or_default()
+}
+
+/// The pane given with `---pane`, the actions apply to the last focused one when it is `None`
+fn target_pane(values: &Values, context: &Context) -> Result<Option<PaneRef>, String> {
+    let Some(query) = values.get("pane") else {
+        return Ok(None);
+    };
+    let pane = find_pane(&context.panes, &query, None)?;
+
+    Ok(Some(PaneRef {
+        id: pane.id,
+        plugin: pane.plugin,
+    }))
+}
+
+fn parse_direction(direction: &str) -> Result<Direction, String> {
+    match direction.to_lowercase().as_str() {
+        "left" => Ok(Direction::Left),
+        "right" => Ok(Direction::Right),
+        "up" => Ok(Direction::Up),
+        "down" => Ok(Direction::Down),
+        _ => Err(format!(
+            "`{direction}` is not a direction: `left`, `right`, `up` or `down`"
+        )),
+    }
+}
+
+fn parse_switch(state: &str) -> Result<bool, String> {
+    match state.to_lowercase().as_str() {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err(format!("`{state}` is neither `on` nor `off`")),
+    }
+}
+
+impl Default for ActionList {
+    fn default() -> Self {
+        ActionList::Unknown {
+            name: String::new(),
+            span: Default::default(),
+            suggestions: Vec::new(),
+        }
+    }
+}
+
+fn deserialize_action(action: &String, variant: &impl EnumMessage) -> bool {
     variant
         .get_serializations()
         .iter()
@@ -197,160 +822,657 @@ fn deserialize_action(action: &String, variant: impl EnumMessage) -> bool {
 }
 
 impl ActionList {
-    fn parse(command: String, interface: &Interface) -> Self {
-        let mut split = command.split_whitespace();
-        let action = split.next().unwrap_or_default().to_lowercase();
-        let mut action_arguments = split.map(|v| v.to_owned());
-
-        match action.as_str() {
-            // Zellij actions
-            // _ if deserialize_action(&action, ActionList::ClearScreen) => ActionList::ClearScreen,
-            // _ if deserialize_action(&action, ActionList::CloseFocus) => ActionList::CloseFocus,
-            // _ if deserialize_action(&action, ActionList::CloseFocusTab) => {
-            //     ActionList::CloseFocusTab
-            // }
-            // _ if deserialize_action(
-            //     &action,
-            //     ActionList::ClosePluginPane {
-            //         id: Default::default(), // TODO: Am I forced to defines every variable. Can’t I just `Default::default()`?                },
-            //     },
-            // ) =>
-            // {
-            //     let id = action_arguments
-            //         .last()
-            //         .map(|s| s.parse::<u32>().ok())
-            //         .unwrap_or_default();
-
-            //     ActionList::ClosePluginPane { id }
-            // }
-            // _ if deserialize_action(
-            //     &action,
-            //     ActionList::CloseTerminalPane {
-            //         id: Default::default(), // TODO: Am I forced to defines every variable. Can’t I just `Default::default()`?                },
-            //     },
-            // ) =>
-            // {
-            //     let id = action_arguments
-            //         .last()
-            //         .map(|s| s.parse::<u32>().ok())
-            //         .unwrap_or_default();
-
-            //     ActionList::CloseTerminalPane { id }
-            // }
-            _ if deserialize_action(&action, ActionList::DetachEveryone) => {
-                ActionList::DetachEveryone
-            }
-            _ if deserialize_action(&action, ActionList::DetachMe) => ActionList::DetachMe,
-            _ if deserialize_action(&action, ActionList::DetachOthers) => ActionList::DetachOthers,
-            // _ if deserialize_action(&action, ActionList::EditScrollback) => {
-            //     ActionList::EditScrollback
-            // }
-            _ if deserialize_action(&action, ActionList::Edit(Default::default())) => {
-                let last = action_arguments.next_back();
-                let line_number = last.clone().unwrap_or(String::new()).parse::<usize>().ok();
-                let mut path = action_arguments.collect::<Vec<String>>().join(" ");
-                if line_number.is_none() {
-                    if !path.is_empty() {
-                        path.push(' ');
-                    }
-                    path.push_str(&last.unwrap_or_default())
-                }
+    /// `piped` when the step comes after a `|`: `Edit` then writes the output in a temporary file if no path is given
+    fn parse(
+        tokens: &[Token],
+        interface: &Interface,
+        context: &Context,
+        validation: Validation,
+        piped: bool,
+    ) -> Self {
+        let Some((token, arguments)) = tokens.split_first() else {
+            return ActionList::default();
+        };
+        let action = token.value.to_lowercase();
 
-                ActionList::Edit(FileToOpen {
-                    path: path.into(),
-                    line_number,
-                    cwd: None, // TODO: get the cwd
-                })
+        let variant = match ActionList::find(&action) {
+            Ok(variant) => variant,
+            Err(candidates) if candidates.is_empty() => {
+                return ActionList::Unknown {
+                    name: token.value.clone(),
+                    span: token.span.clone(),
+                    // A name coming from a variable or an alias cannot be corrected in the prompt
+                    suggestions: if token.expanded || validation == Validation::Deferred {
+                        Vec::new()
+                    } else {
+                        ActionList::suggestions(&action)
+                    },
+                };
             }
-            _ if deserialize_action(
-                &action,
-                ActionList::NewPane {
-                    path: Default::default(),
-                },
-            ) =>
-            {
-                ActionList::NewPane {
-                    path: action_arguments.collect::<Vec<String>>().join(" "),
+            Err(candidates) => {
+                return ActionList::Ambiguous {
+                    name: token.value.clone(),
+                    candidates: candidates
+                        .iter()
+                        .filter_map(|c| c.get_serializations().first())
+                        .map(|c| c.to_string())
+                        .collect(),
                 }
             }
-            _ if deserialize_action(&action, ActionList::Run(Default::default())) => {
-                let mut cmd = String::new();
-                let mut args: Vec<String> = Default::default();
-                let mut cwd = Default::default();
-
-                let mut val = action_arguments.next();
-                while val.is_some() {
-                    // Safety: checked in the while loop
-                    let v = unsafe { val.unwrap_unchecked() };
-
-                    match v.as_str() {
-                        "---cwd" => cwd = action_arguments.next().map(PathBuf::from),
-                        _ => {
-                            if cmd.is_empty() {
-                                cmd = v;
-                            } else {
-                                args.push(v);
-                            }
-                        }
+        };
+
+        // Errors about the values as a whole point to all of them, or to the action name if there are none
+        let span = match arguments.last() {
+            Some(last) => arguments[0].span.start..last.span.end,
+            None => token.span.clone(),
+        };
+        let arguments = match variant {
+            Self::Edit { .. } => split_location(arguments),
+            Self::Job { .. } | Self::Run { .. } => split_assignments(arguments),
+            _ => arguments.to_vec(),
+        };
+        let action = bind(variant.arguments(), &arguments)
+            .and_then(|values| {
+                let missing = variant
+                    .arguments()
+                    .iter()
+                    .filter(|a| !(piped && a.name == "paths"))
+                    .find(|a| a.required && values.get(a.name).is_none());
+                match missing {
+                    Some(missing) => Err(ParseError {
+                        message: format!(
+                            "Missing `{}`, usage: `{}`",
+                            missing.usage(),
+                            variant.usage()
+                        ),
+                        span: span.end..span.end,
+                    }),
+                    None => Ok(values),
+                }
+            })
+            .and_then(|values| {
+                variant
+                    .with_values(&values, interface, context, validation)
+                    .map_err(|message| ParseError { message, span })
+            });
+
+        action.unwrap_or_else(|error| ActionList::Invalid { error })
+    }
+
+    /// The action named `name` or that it abbreviates, else all the actions it abbreviates (none if it is unknown)
+    fn find(name: &str) -> Result<ActionList, Vec<ActionList>> {
+        let name = name.to_lowercase();
+        if let Some(variant) = ActionList::filter_any().find(|v| deserialize_action(&name, v)) {
+            return Ok(variant);
+        }
+
+        let mut candidates: Vec<_> = ActionList::filter_any()
+            .filter(|v| v.get_serializations().iter().any(|s| abbreviates(&name, s)))
+            .collect();
+        if candidates.len() == 1 {
+            Ok(candidates.remove(0))
+        } else {
+            Err(candidates)
+        }
+    }
+
+    /// Canonical names of the actions close to `name`, the closest first
+    fn suggestions(name: &str) -> Vec<String> {
+        let mut candidates: Vec<_> = ActionList::filter_any()
+            .filter_map(|variant| {
+                let serializations = variant.get_serializations();
+                let canonical = serializations.first()?;
+                let distance = serializations
+                    .iter()
+                    .map(|serialization| prefix_distance(name, serialization))
+                    .min()?;
+                (distance <= tolerance(name)).then(|| (distance, canonical.to_string()))
+            })
+            .collect();
+        candidates.sort();
+
+        candidates
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    /// The arguments this action accepts, used to parse, display and document it
+    pub(crate) fn arguments(&self) -> &'static [Argument] {
+        match self {
+            Self::HelpAll { .. } | Self::HelpPane { .. } | Self::HelpPipe { .. } => HELP_ARGUMENTS,
+            Self::ClearScreen { .. } | Self::EditScrollback { .. } => TARGET_PANE_ARGUMENTS,
+            Self::ClosePluginPane { .. } | Self::CloseTerminalPane { .. } => CLOSE_PANE_ARGUMENTS,
+            Self::CloseTab { .. } => CLOSE_TAB_ARGUMENTS,
+            Self::Edit { .. } => EDIT_ARGUMENTS,
+            Self::Embed { .. } | Self::Float { .. } | Self::Fullscreen { .. } => &[PANE_ARGUMENT],
+            Self::Focus { .. } => FOCUS_ARGUMENTS,
+            Self::Frames { .. } => FRAMES_ARGUMENTS,
+            Self::GoToTab { .. } => GO_TO_TAB_ARGUMENTS,
+            Self::Macro { .. } => MACRO_ARGUMENTS,
+            Self::Move { .. } => MOVE_ARGUMENTS,
+            Self::MoveTab { .. } => MOVE_TAB_ARGUMENTS,
+            Self::NewPane { .. } => NEW_PANE_ARGUMENTS,
+            Self::NewTab { .. } => NEW_TAB_ARGUMENTS,
+            Self::RenameTab { .. } => RENAME_TAB_ARGUMENTS,
+            Self::Resize { .. } => RESIZE_ARGUMENTS,
+            Self::Job { .. } | Self::Run { .. } => RUN_ARGUMENTS,
+            Self::KillJob { .. } => KILL_JOB_ARGUMENTS,
+            Self::Unknown { .. }
+            | Self::Unavailable { .. }
+            | Self::Invalid { .. }
+            | Self::Ambiguous { .. }
+            | Self::DetachEveryone
+            | Self::DetachMe
+            | Self::DetachOthers
+            | Self::Jobs
+            | Self::ClearJobs => &[],
+        }
+    }
+
+    /// The kind of pane `Type::Pane` arguments are searched in: plugin (`Some(true)`), terminal or any
+    fn pane_kind(&self) -> Option<bool> {
+        match self {
+            Self::ClosePluginPane { .. } => Some(true),
+            Self::ClearScreen { .. }
+            | Self::CloseTerminalPane { .. }
+            | Self::EditScrollback { .. } => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Build this action from the values given to its `arguments`
+    fn with_values(
+        self,
+        values: &Values,
+        interface: &Interface,
+        context: &Context,
+        validation: Validation,
+    ) -> Result<Self, String> {
+        let action = match self {
+            Self::Edit { .. } => {
+                let cwd = values.get("cwd").map(PathBuf::from);
+                let mut column = values.get_number("column");
+
+                let mut files = Vec::new();
+                for (path, literal) in values.get_all_literal("paths") {
+                    // Paths written after `--` are kept as is, in case they really contain a `:`
+                    let (path, line, path_column) = if literal {
+                        (path.as_str(), None, None)
+                    } else {
+                        split_position(&path)
                     };
-                    val = action_arguments.next();
+                    let line_number = values.get_number("line").or(line);
+                    column = column.or(path_column);
+                    let path = path.to_string();
+
+                    // Searching the disk is too slow to be done on each key press
+                    if validation == Validation::Full && host::is_glob(&path) {
+                        let folder = context.initial_cwd.join(cwd.clone().unwrap_or_default());
+                        let matches = host::expand_glob(&path, &folder, &context.initial_cwd)?;
+                        files.extend(matches.into_iter().map(|path| FileToOpen {
+                            path,
+                            line_number,
+                            cwd: None,
+                        }));
+                    } else {
+                        files.push(FileToOpen {
+                            path: path.into(),
+                            line_number,
+                            cwd: cwd.clone(),
+                        });
+                    }
                 }
 
-                ActionList::Run(CommandToRun {
-                    path: cmd.into(),
-                    args,
-                    cwd,
-                })
+                Self::Edit {
+                    files,
+                    column,
+                    new_tab: values.is_set("tab"),
+                    confirmed: values.is_set("yes"),
+                }
+            }
+            Self::Macro { .. } => {
+                let name = values.get("name").unwrap_or_default();
+                match context.macros.get(&name) {
+                    Some(steps) => Self::Macro {
+                        name,
+                        steps: steps.clone(),
+                    },
+                    None => {
+                        let available = context
+                            .macros
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let message = match (name.is_empty(), available.is_empty()) {
+                            (_, true) => String::from("No macro is defined in the configuration"),
+                            (true, false) => format!("Available macros: {available}"),
+                            (false, false) => {
+                                format!("Unknown macro `{name}`, available macros: {available}")
+                            }
+                        };
+                        return Err(message);
+                    }
+                }
             }
+            Self::NewPane { .. } => Self::NewPane {
+                path: values.get("path").unwrap_or_default(),
+            },
+            Self::NewTab { .. } => {
+                let name = values.get("name");
+                if let Some(name) = name.as_ref().filter(|name| context.tabs.contains(name)) {
+                    return Err(format!("There is already a tab named `{name}`"));
+                }
+                Self::NewTab {
+                    name,
+                    cwd: values.get("cwd").map(PathBuf::from),
+                }
+            }
+            Self::ClearScreen { .. } | Self::EditScrollback { .. } => {
+                let pane = values
+                    .get("pane")
+                    .map(|pane| find_pane(&context.panes, &pane, self.pane_kind()))
+                    .transpose()?
+                    .map(|pane| pane.id);
+                match self {
+                    Self::ClearScreen { .. } => Self::ClearScreen { pane },
+                    _ => Self::EditScrollback { pane },
+                }
+            }
+            Self::ClosePluginPane { .. } | Self::CloseTerminalPane { .. } => {
+                let pane = values.get("pane").unwrap_or_default();
+                let id = find_pane(&context.panes, &pane, self.pane_kind())?.id;
+                match self {
+                    Self::ClosePluginPane { .. } => Self::ClosePluginPane { id },
+                    _ => Self::CloseTerminalPane { id },
+                }
+            }
+            Self::Embed { .. } | Self::Float { .. } | Self::Fullscreen { .. } => {
+                let pane = target_pane(values, context)?;
+                match self {
+                    Self::Embed { .. } => Self::Embed { pane },
+                    Self::Float { .. } => Self::Float { pane },
+                    _ => Self::Fullscreen { pane },
+                }
+            }
+            Self::Frames { .. } => Self::Frames {
+                shown: values
+                    .get("state")
+                    .map(|state| parse_switch(&state))
+                    .transpose()?,
+            },
+            Self::Move { .. } => Self::Move {
+                direction: values
+                    .get("direction")
+                    .map(|direction| parse_direction(&direction))
+                    .transpose()?,
+                pane: target_pane(values, context)?,
+            },
+            Self::Resize { .. } => {
+                let mut direction = values.get("direction");
+                let mut amount = values.get("amount").unwrap_or_default();
+                // `Resize +5` grows every side
+                if !values.is_set("amount")
+                    && direction
+                        .as_ref()
+                        .is_some_and(|d| d.starts_with(['+', '-']))
+                {
+                    amount = direction.take().unwrap_or_default();
+                }
+                let amount = match amount.as_str() {
+                    "+" => 1,
+                    "-" => -1,
+                    amount => amount
+                        .parse()
+                        .map_err(|_| format!("`{amount}` is not an amount like `+5` or `-2`"))?,
+                };
 
-            // Technicals
-            _ if deserialize_action(
-                &action,
-                ActionList::HelpAll {
-                    selection: Default::default(),
-                },
-            ) =>
-            {
-                let max = ActionList::filter_any().count();
-                let selection = match interface {
-                    Interface::All | Interface::Pane => Selection::One { max, row: 0 },
-                    Interface::Pipe => Default::default(),
+                Self::Resize {
+                    amount,
+                    direction: direction
+                        .map(|direction| parse_direction(&direction))
+                        .transpose()?,
+                    pane: target_pane(values, context)?,
+                }
+            }
+            Self::CloseTab { .. } => Self::CloseTab {
+                position: values
+                    .get("tab")
+                    .map(|tab| find_tab(&context.tabs, &tab, false))
+                    .transpose()?,
+            },
+            Self::RenameTab { .. } => Self::RenameTab {
+                name: values.get("name").unwrap_or_default(),
+                position: values
+                    .get("tab")
+                    .map(|tab| find_tab(&context.tabs, &tab, false))
+                    .transpose()?,
+            },
+            Self::Focus { .. } => {
+                let filter = values.get_all("filter");
+                let panes = context
+                    .panes
+                    .iter()
+                    .filter(|pane| pane.selectable && filter.iter().all(|word| pane.matches(word)))
+                    .cloned()
+                    .collect();
+                Self::Focus {
+                    filter,
+                    panes,
+                    selected: 0,
+                }
+            }
+            Self::GoToTab { .. } => Self::GoToTab {
+                position: find_tab(&context.tabs, &values.get("tab").unwrap_or_default(), true)?,
+            },
+            Self::MoveTab { .. } => {
+                let to = values.get("to").unwrap_or_default();
+                let offset = match to.to_lowercase().as_str() {
+                    "left" => -1,
+                    "right" => 1,
+                    _ => {
+                        let target = find_tab(&context.tabs, &to, false)?;
+                        let focused = context
+                            .focused_tab
+                            .ok_or_else(|| String::from("The focused tab is not known yet"))?;
+                        target as isize - focused as isize
+                    }
                 };
+                Self::MoveTab { offset }
+            }
+            Self::Job { .. } | Self::Run { .. } => {
+                let cwd = values.get("cwd").map(PathBuf::from);
+                let env_file = values.get("env-file");
+
+                let mut env = BTreeMap::new();
+                // Reading the disk is too slow to be done on each key press
+                if let Some(path) = env_file.as_ref().filter(|_| validation == Validation::Full) {
+                    let folder = context.initial_cwd.join(cwd.clone().unwrap_or_default());
+                    env.extend(host::read_env_file(path, &folder, &context.initial_cwd)?);
+                }
+                for assignment in values.get_all("env") {
+                    if let Some((key, value)) = split_assignment(&assignment) {
+                        env.insert(key.to_string(), value.to_string());
+                    }
+                }
 
-                ActionList::HelpAll { selection }
+                let command = CommandToRun {
+                    path: values.get("command").unwrap_or_default().into(),
+                    args: values.get_all("args"),
+                    cwd,
+                };
+                let env_file = env_file.map(PathBuf::from);
+                match self {
+                    Self::Job { .. } => Self::Job {
+                        command,
+                        env,
+                        env_file,
+                    },
+                    _ => Self::Run {
+                        command,
+                        env,
+                        env_file,
+                    },
+                }
             }
-            _ if deserialize_action(
-                &action,
-                ActionList::HelpPane {
-                    selection: Default::default(),
-                },
-            ) =>
-            {
-                let max = ActionList::filter_pane().count();
+            Self::KillJob { .. } => Self::KillJob {
+                id: values.get_number("id"),
+            },
+            Self::HelpAll { .. } | Self::HelpPane { .. } | Self::HelpPipe { .. } => {
+                let max = match self {
+                    Self::HelpAll { .. } => {
+                        ActionList::filter_any().count() + context.aliases.len()
+                    }
+                    Self::HelpPane { .. } => ActionList::filter_pane().count(),
+                    _ => ActionList::filter_pipe().count(),
+                };
                 let selection = match interface {
+                    _ if values.is_set("expand") => Selection::Expand,
                     Interface::All | Interface::Pane => Selection::One { max, row: 0 },
                     Interface::Pipe => Default::default(),
                 };
 
-                ActionList::HelpPane { selection }
+                match self {
+                    Self::HelpAll { .. } => Self::HelpAll {
+                        selection,
+                        aliases: context.aliases.clone(),
+                    },
+                    Self::HelpPane { .. } => Self::HelpPane { selection },
+                    _ => Self::HelpPipe { selection },
+                }
+            }
+            _ => self,
+        };
+
+        Ok(action)
+    }
+
+    /// How to write this action: `Run <COMMAND> [ARGS...] [---cwd DIR]`
+    pub(crate) fn usage(&self) -> String {
+        let name = self
+            .get_serializations()
+            .first()
+            .expect("At least one serialization is guaranteed");
+
+        self.arguments()
+            .iter()
+            .fold(name.to_string(), |accu, argument| {
+                format!("{accu} {}", argument.usage())
+            })
+    }
+
+    /// The current value of the `arguments`, by name, as shown to the user
+    pub(crate) fn values(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Self::Edit {
+                files,
+                column,
+                new_tab,
+                confirmed,
+            } => {
+                let first = files.first().cloned().unwrap_or_default();
+                vec![
+                    (
+                        "paths",
+                        match &files[..] {
+                            [] | [_] => first.path.display().to_string(),
+                            _ => format!("{} files", files.len()),
+                        },
+                    ),
+                    (
+                        "line",
+                        first.line_number.map(|l| l.to_string()).unwrap_or_default(),
+                    ),
+                    ("column", column.map(|c| c.to_string()).unwrap_or_default()),
+                    (
+                        "cwd",
+                        first
+                            .cwd
+                            .map(|c| c.display().to_string())
+                            .unwrap_or_default(),
+                    ),
+                    ("tab", new_tab.to_string()),
+                    ("yes", confirmed.to_string()),
+                ]
+            }
+            Self::HelpAll { selection, .. }
+            | Self::HelpPane { selection }
+            | Self::HelpPipe { selection } => {
+                vec![("expand", matches!(selection, Selection::Expand).to_string())]
             }
-            _ if deserialize_action(
-                &action,
-                ActionList::HelpPipe {
-                    selection: Default::default(),
+            Self::KillJob { id } => vec![("id", id.map(|id| id.to_string()).unwrap_or_default())],
+            Self::Macro { name, .. } => vec![("name", name.clone())],
+            Self::NewPane { path } => vec![("path", path.clone())],
+            Self::NewTab { name, cwd } => vec![
+                ("name", name.clone().unwrap_or_default()),
+                (
+                    "cwd",
+                    cwd.as_ref()
+                        .map(|c| c.display().to_string())
+                        .unwrap_or_default(),
+                ),
+            ],
+            Self::ClearScreen { pane } | Self::EditScrollback { pane } => {
+                vec![("pane", pane.map(|id| id.to_string()).unwrap_or_default())]
+            }
+            Self::ClosePluginPane { id } | Self::CloseTerminalPane { id } => {
+                vec![("pane", id.to_string())]
+            }
+            Self::CloseTab { position } => vec![(
+                "tab",
+                position.map(|p| (p + 1).to_string()).unwrap_or_default(),
+            )],
+            Self::RenameTab { name, position } => vec![
+                ("name", name.clone()),
+                (
+                    "tab",
+                    position.map(|p| (p + 1).to_string()).unwrap_or_default(),
+                ),
+            ],
+            Self::Embed { pane } | Self::Float { pane } | Self::Fullscreen { pane } => {
+                vec![("pane", describe_target(pane))]
+            }
+            Self::Focus { filter, .. } => vec![("filter", filter.join(" "))],
+            Self::Frames { shown } => vec![(
+                "state",
+                match shown {
+                    Some(true) => String::from("on"),
+                    Some(false) => String::from("off"),
+                    None => String::new(),
                 },
-            ) =>
-            {
-                let max = ActionList::filter_pipe().count();
-                let selection = match interface {
-                    Interface::All | Interface::Pane => Selection::One { max, row: 0 },
-                    Interface::Pipe => Default::default(),
-                };
+            )],
+            Self::Move { direction, pane } => vec![
+                (
+                    "direction",
+                    direction.map(|d| format!("{d:?}")).unwrap_or_default(),
+                ),
+                ("pane", describe_target(pane)),
+            ],
+            Self::Resize {
+                direction,
+                amount,
+                pane,
+            } => vec![
+                (
+                    "direction",
+                    direction.map(|d| format!("{d:?}")).unwrap_or_default(),
+                ),
+                ("amount", format!("{amount:+}")),
+                ("pane", describe_target(pane)),
+            ],
+            Self::GoToTab { position } => vec![("tab", (position + 1).to_string())],
+            Self::MoveTab { offset } => vec![("to", offset.to_string())],
+            Self::Job {
+                command: CommandToRun { path, args, cwd },
+                env,
+                env_file,
+            }
+            | Self::Run {
+                command: CommandToRun { path, args, cwd },
+                env,
+                env_file,
+            } => vec![
+                (
+                    "env",
+                    env.iter()
+                        .map(|(key, value)| format!("{key}={}", quote(value)))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+                (
+                    "env-file",
+                    env_file
+                        .as_ref()
+                        .map(|f| f.display().to_string())
+                        .unwrap_or_default(),
+                ),
+                ("command", path.display().to_string()),
+                ("args", format!("{args:?}")),
+                (
+                    "cwd",
+                    cwd.as_ref()
+                        .map(|c| c.display().to_string())
+                        .unwrap_or_default(),
+                ),
+            ],
+            _ => vec![],
+        }
+    }
 
-                ActionList::HelpPipe { selection }
+    /// Keep the row selected in the `previous` parse of the same action
+    fn keep_selection(&mut self, previous: &ActionList) {
+        match (self, previous) {
+            (
+                Self::Focus {
+                    panes, selected, ..
+                },
+                Self::Focus {
+                    panes: previous_panes,
+                    selected: previous_selected,
+                    ..
+                },
+            ) => {
+                // The same pane, even if the list changed
+                *selected = previous_panes
+                    .get(*previous_selected)
+                    .and_then(|previous| {
+                        panes
+                            .iter()
+                            .position(|p| p.id == previous.id && p.plugin == previous.plugin)
+                    })
+                    .unwrap_or((*previous_selected).min(panes.len().saturating_sub(1)));
             }
+            (
+                Self::HelpAll { selection, .. },
+                Self::HelpAll {
+                    selection: previous,
+                    ..
+                },
+            )
+            | (
+                Self::HelpPane { selection },
+                Self::HelpPane {
+                    selection: previous,
+                },
+            )
+            | (
+                Self::HelpPipe { selection },
+                Self::HelpPipe {
+                    selection: previous,
+                },
+            ) => {
+                if let (Selection::One { row, max }, Selection::One { row: previous, .. }) =
+                    (selection, previous)
+                {
+                    *row = (*previous).min(max.saturating_sub(1));
+                }
+            }
+            _ => {}
+        }
+    }
 
-            _ => ActionList::Unknown,
+    /// Wrap this action in `Unavailable` if it cannot be used from the `interface`
+    fn restrict(self, interface: &Interface) -> Self {
+        let usable = match self {
+            ActionList::Unknown { .. }
+            | ActionList::Invalid { .. }
+            | ActionList::Ambiguous { .. } => true,
+            _ => match interface {
+                Interface::All => true,
+                Interface::Pane => self.usable_in_pane(),
+                Interface::Pipe => self.usable_in_pipe(),
+            },
+        };
+
+        if usable {
+            self
+        } else {
+            ActionList::Unavailable {
+                action: Box::new(self),
+                calling_interface: *interface,
+            }
         }
     }
 
@@ -381,10 +1503,6 @@ impl ActionList {
         ActionList::iter().filter(Self::usable_in_any)
     }
 
-    pub(crate) fn filter_all() -> impl Iterator<Item = ActionList> {
-        ActionList::iter().filter(Self::usable_in_all)
-    }
-
     pub(crate) fn filter_pane() -> impl Iterator<Item = ActionList> {
         ActionList::iter().filter(Self::usable_in_pane)
     }
@@ -394,78 +1512,240 @@ impl ActionList {
     }
 }
 
-// TODO: use self referential struct? So we do not store the data twice since `action` is computed from `command`
-#[derive(Debug, Clone)]
+/// How much checking is done while parsing
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Validation {
+    /// Everything is checked
+    #[default]
+    Full,
+    /// The slow checks (Ex: looking for suggestions) are skipped while the user is typing
+    Deferred,
+}
+
+/// Why a command could not be read
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ParseError {
+    pub(crate) message: String,
+    /// Byte range of the offending part of the prompt, empty when something is missing at its end
+    pub(crate) span: Range<usize>,
+}
+
+/// The `$VARIABLES` that can be used in the commands, and what they hold.
+/// Zellij does not tell the plugins the cwd of the panes, so there is no `$FOCUSED_CWD`.
+pub(crate) const VARIABLES: &[(&str, &str)] = &[
+    ("INITIAL_CWD", "Folder the session started in"),
+    (
+        "FOCUSED_PANE_ID",
+        "Id of the last pane focused before the console",
+    ),
+    ("TAB_NAME", "Name of the focused tab"),
+    ("SESSION_NAME", "Name of the session"),
+    ("LAST_FILE", "Last file opened with `Edit`"),
+];
+
+/// What the parser needs to know about the session and the user’s configuration
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Context {
+    /// Values of the `$VARIABLES` that can be used in the commands
+    pub(crate) variables: BTreeMap<String, String>,
+    /// The user’s aliases (lowercase) and the command they stand for
+    pub(crate) aliases: BTreeMap<String, String>,
+    /// The user’s macros and the commands they run
+    pub(crate) macros: BTreeMap<String, Vec<String>>,
+    /// Where the session started, the only folder the plugin can read (mounted on `/host`)
+    pub(crate) initial_cwd: PathBuf,
+    /// The programs found in the `$PATH`, sorted
+    pub(crate) executables: Vec<String>,
+    /// Names of the tabs, in their order
+    pub(crate) tabs: Vec<String>,
+    /// Position of the focused tab, from 0
+    pub(crate) focused_tab: Option<usize>,
+    pub(crate) panes: Vec<Pane>,
+}
+
+/// A pane of the session, as the parser needs it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Pane {
+    /// Unique among the panes of the same kind
+    pub(crate) id: u32,
+    pub(crate) plugin: bool,
+    pub(crate) title: String,
+    /// The program of a command pane, or the name of a plugin
+    pub(crate) command: Option<String>,
+    /// Name of the tab the pane is in
+    pub(crate) tab: String,
+    /// Only command panes are kept once exited
+    pub(crate) exited: bool,
+    pub(crate) exit_status: Option<i32>,
+    /// The tab bar and the status bar cannot be focused
+    pub(crate) selectable: bool,
+}
+
+impl Pane {
+    /// What the user can call this pane by, besides its id
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.title.as_str()).chain(self.command.as_deref())
+    }
+
+    /// Whether a `word` of the filter of `Focus` is in the tab, the title or the command of the pane
+    fn matches(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        std::iter::once(self.tab.as_str())
+            .chain(self.names())
+            .any(|name| name.to_lowercase().contains(&word) || abbreviates(&word, name))
+    }
+
+    /// The id of the pane among all the panes: `terminal_3` or `plugin_3`
+    fn qualified_id(&self) -> String {
+        let kind = if self.plugin { "plugin" } else { "terminal" };
+        format!("{kind}_{}", self.id)
+    }
+
+    /// How the pane is listed when several ones match: `3 (cargo watch)`, or `terminal_3 (cargo watch)` when `qualified`
+    fn describe(&self, qualified: bool) -> String {
+        let id = if qualified {
+            self.qualified_id()
+        } else {
+            self.id.to_string()
+        };
+        format!("{id} ({})", self.title)
+    }
+}
+
+/// Candidates to complete the argument being written, listed under the prompt
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Completion {
+    /// Part of the prompt replaced by the chosen candidate
+    span: Range<usize>,
+    pub(crate) candidates: Vec<String>,
+    pub(crate) selected: usize,
+}
+
+/// Where the end of the prompt is in the step being written
+struct Cursor {
+    variant: ActionList,
+    /// The arguments as they are bound, the variables before the program of `Run` are rewritten
+    arguments: Vec<Token>,
+    /// Index in the `arguments` of the one being written, `None` while it is the name of the action
+    index: Option<usize>,
+}
+
+// TODO: use self referential struct? So we do not store the data twice since `sequence` is computed from `command`
+#[derive(Debug, Clone, Default)]
 pub(crate) struct Action {
     command: String,
-    action: ActionList,
+    sequence: Sequence,
+    context: Context,
+    /// Interface of the last edit, to parse again when the `context` changes
+    interface: Interface,
+    /// Tokens of the `command`, the tokenizer resumes from `checkpoint` after an edit
+    tokens: Vec<Token>,
+    checkpoint: Checkpoint,
+    /// Open when several candidates can complete the argument being written
+    completion: Option<Completion>,
 }
 
 impl Action {
-    pub(crate) fn action(&self) -> &ActionList {
-        &self.action
-    }
-
-    pub(crate) fn command(&self) -> String {
-        self.command.clone()
+    pub(crate) fn sequence(&self) -> &Sequence {
+        &self.sequence
     }
 }
 
 impl Action {
-    fn parse_action(&mut self, interface: &Interface) {
-        // TODO: maybe don’t reparse all each time?
-        // if charactere.is_whitespace() {
-        let a = ActionList::parse(self.command.clone(), &interface);
-        if let ActionList::Unknown = a {
-            self.action = a;
-            return;
+    /// Parse the command again, `unchanged` is the length of its start that was not edited
+    fn parse_action(&mut self, interface: &Interface, unchanged: usize, validation: Validation) {
+        let (previous_tokens, checkpoint) = if self.checkpoint.position <= unchanged {
+            (self.tokens.as_slice(), self.checkpoint)
+        } else {
+            (&[][..], Checkpoint::default())
+        };
+        let tokenized = tokenize_from(
+            &self.command,
+            &self.context.variables,
+            previous_tokens,
+            checkpoint,
+        );
+
+        // The steps parsed for another interface are restricted differently
+        let previous = (self.interface == *interface).then_some(&self.sequence);
+        self.sequence = Sequence::from_tokens(
+            &self.command,
+            tokenized.clone().map(|(tokens, _)| tokens),
+            interface,
+            &self.context,
+            validation,
+            previous,
+        );
+        (self.tokens, self.checkpoint) = tokenized.unwrap_or_default();
+        self.interface = *interface;
+        // The candidates were for the previous command
+        self.completion = None;
+    }
+
+    /// Do the checks that were skipped while typing, return whether there were some
+    pub(crate) fn validate(&mut self) -> bool {
+        if self.sequence.is_validated() {
+            return false;
         }
 
-        self.action = match &interface {
-            Interface::All => a,
-            Interface::Pane => {
-                if a.usable_in_pane() {
-                    a
-                } else {
-                    ActionList::Unavailable {
-                        action: Box::new(a),
-                        calling_interface: *interface,
-                    }
-                }
-            }
-            Interface::Pipe => {
-                if a.usable_in_pipe() {
-                    a
-                } else {
-                    ActionList::Unavailable {
-                        action: Box::new(a),
-                        calling_interface: *interface,
-                    }
-                }
-            }
+        self.reparse(self.command.len(), false);
+        true
+    }
+
+    /// Parse the same command again, keeping what the user selected in the candidates and the lists.
+    /// Nothing parsed before can be reused when the `context_changed`.
+    fn reparse(&mut self, unchanged: usize, context_changed: bool) {
+        let completion = self.completion.take();
+        let previous = self.sequence.last().clone();
+        if context_changed {
+            self.sequence = Sequence::default();
         }
-        // }
+        let interface = self.interface;
+        self.parse_action(&interface, unchanged, Validation::Full);
+
+        self.completion = completion;
+        self.sequence.last_mut().keep_selection(&previous);
+    }
+
+    pub(crate) fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub(crate) fn update_context(&mut self, update: impl FnOnce(&mut Context)) {
+        update(&mut self.context);
+        self.reparse(0, true);
     }
 
     pub(crate) fn set(&mut self, command: &str, interface: &Interface) {
+        let unchanged = self
+            .command
+            .char_indices()
+            .zip(command.chars())
+            .find(|((_, old), new)| old != new)
+            .map_or(self.command.len().min(command.len()), |((i, _), _)| i);
         self.command = command.to_string();
-        self.parse_action(interface);
+        self.parse_action(interface, unchanged, Validation::Full);
     }
 
     // Emulate a `String`
     pub(crate) fn push(&mut self, charactere: char, interface: &Interface) {
+        let unchanged = self.command.len();
         self.command.push(charactere);
-        self.parse_action(interface);
+        self.parse_action(interface, unchanged, Validation::Deferred);
     }
     pub(crate) fn pop(&mut self, interface: &Interface) -> Option<char> {
         let res = self.command.pop();
-        self.parse_action(interface);
+        self.parse_action(interface, self.command.len(), Validation::Deferred);
         res
     }
+    /// Run the actions even if they are not available on the calling interface
+    pub(crate) fn force(&mut self) {
+        self.sequence.force();
+    }
     pub(crate) fn clear(&mut self) {
         self.command.clear();
         // We clear the action so don’t care from which interface. Improvise some value instead of requesting one from the caller.
-        self.parse_action(&Interface::All);
+        self.parse_action(&Interface::All, 0, Validation::Full);
     }
     pub(crate) fn len(&self) -> usize {
         self.command.len()
@@ -474,9 +1754,231 @@ impl Action {
         &self.command
     }
 
+    /// Replace the first unknown action name by the closest action, return whether there was one
+    pub(crate) fn accept_suggestion(&mut self, interface: &Interface) -> bool {
+        self.validate();
+        let replacement = self
+            .sequence
+            .steps()
+            .iter()
+            .find_map(|step| match &step.action {
+                ActionList::Unknown {
+                    span, suggestions, ..
+                } => suggestions.first().map(|s| (span.clone(), s.clone())),
+                _ => None,
+            });
+
+        let Some((span, suggestion)) = replacement else {
+            return false;
+        };
+        let unchanged = span.start;
+        self.command.replace_range(span, &suggestion);
+        self.parse_action(interface, unchanged, Validation::Full);
+        true
+    }
+
+    /// The rest of the most likely command: from the `history`, or else the name of an action
+    pub(crate) fn autosuggestion(&self, history: &History) -> Option<String> {
+        if self.command.is_empty() || self.completion.is_some() {
+            return None;
+        }
+
+        if let Some(entry) = history.suggestion(&self.command) {
+            return entry.get(self.command.len()..).map(String::from);
+        }
+
+        // Only the name of the action can be suggested without history
+        if self.command.contains(char::is_whitespace) {
+            return None;
+        }
+        let start = self.command.to_lowercase();
+        ActionList::filter_any()
+            .filter_map(|variant| variant.get_serializations().first().copied())
+            .find(|name| name.len() > start.len() && name.to_lowercase().starts_with(&start))
+            .and_then(|name| name.get(self.command.len()..))
+            .map(String::from)
+    }
+
+    /// Write the rest of the `autosuggestion`, return whether there was one
+    pub(crate) fn accept_autosuggestion(
+        &mut self,
+        history: &History,
+        interface: &Interface,
+    ) -> bool {
+        let Some(rest) = self.autosuggestion(history) else {
+            return false;
+        };
+
+        let unchanged = self.command.len();
+        self.command.push_str(&rest);
+        self.parse_action(interface, unchanged, Validation::Deferred);
+        true
+    }
+
+    pub(crate) fn completion(&self) -> Option<&Completion> {
+        self.completion.as_ref()
+    }
+
+    /// Complete the argument being written, return whether there was something to complete.
+    /// When there are several candidates, they are listed first and the selected one is used on the next call.
+    pub(crate) fn complete(&mut self, interface: &Interface) -> bool {
+        if let Some(completion) = self.completion.take() {
+            let candidate = &completion.candidates[completion.selected];
+            self.replace(completion.span.clone(), candidate, interface);
+            return true;
+        }
+
+        let Some(completion) = self.completion_candidates() else {
+            return false;
+        };
+        match &completion.candidates[..] {
+            [] => false,
+            [candidate] => {
+                self.replace(completion.span.clone(), candidate, interface);
+                true
+            }
+            _ => {
+                self.completion = Some(completion);
+                true
+            }
+        }
+    }
+
+    /// Put a completed `value` in place of the `span` of the command
+    fn replace(&mut self, span: Range<usize>, value: &str, interface: &Interface) {
+        let unchanged = span.start;
+        let mut value = quote(value);
+        // Folders are completed further, anything else is done
+        if !value.ends_with('/') {
+            value.push(' ');
+        }
+        self.command.replace_range(span, &value);
+        self.parse_action(interface, unchanged, Validation::Deferred);
+    }
+
+    /// Where the end of the prompt is in the step being written
+    fn cursor(&self) -> Option<Cursor> {
+        let step = self.sequence.steps().last()?;
+        let (name, arguments) = step.tokens().split_first()?;
+        let variant = ActionList::find(&name.value).ok()?;
+
+        // A new argument is started when the command ends with a space
+        let end = self.command.len();
+        let index = match arguments.last() {
+            Some(last) if last.span.end == end => Some(arguments.len() - 1),
+            _ if name.span.end == end => None,
+            _ => Some(arguments.len()),
+        };
+
+        // Each variable before the program gets a `---env` inserted before it
+        let (arguments, index) = match variant {
+            ActionList::Job { .. } | ActionList::Run { .. } => {
+                let assignments = leading_assignments(arguments);
+                let index = index.map(|i| i + assignments.iter().filter(|&&j| j <= i).count());
+                (split_assignments(arguments), index)
+            }
+            _ => (arguments.to_vec(), index),
+        };
+
+        Some(Cursor {
+            variant,
+            arguments,
+            index,
+        })
+    }
+
+    /// The action being written, and the argument the cursor is in
+    pub(crate) fn signature(&self) -> Option<(ActionList, Option<&'static Argument>)> {
+        let cursor = self.cursor()?;
+        let argument = cursor
+            .index
+            .and_then(|index| argument_of(cursor.variant.arguments(), &cursor.arguments, index));
+
+        Some((cursor.variant, argument))
+    }
+
+    fn completion_candidates(&self) -> Option<Completion> {
+        let Cursor {
+            variant,
+            arguments,
+            index,
+        } = self.cursor()?;
+        let index = index?;
+
+        let (span, prefix) = match arguments.get(index) {
+            // The words of an alias or with variables are not written as is in the prompt
+            Some(token) if token.expanded => return None,
+            Some(token) => (token.span.clone(), token.value.as_str()),
+            None => (self.command.len()..self.command.len(), ""),
+        };
+
+        let argument = argument_of(variant.arguments(), &arguments, index)?;
+        let cwd = bind(variant.arguments(), &arguments)
+            .ok()
+            .and_then(|values| values.get("cwd"))
+            .filter(|_| argument.name != "cwd");
+        let folder = self.context.initial_cwd.join(cwd.unwrap_or_default());
+        let candidates = match argument.value {
+            Type::Path => host::complete_path(prefix, &folder, &self.context.initial_cwd, false),
+            Type::Directory => {
+                host::complete_path(prefix, &folder, &self.context.initial_cwd, true)
+            }
+            Type::Executable if prefix.contains('/') => {
+                host::complete_path(prefix, &folder, &self.context.initial_cwd, false)
+            }
+            Type::Executable => self
+                .context
+                .executables
+                .iter()
+                .filter(|executable| executable.starts_with(prefix))
+                .cloned()
+                .collect(),
+            Type::Pane => {
+                let plugin = variant.pane_kind();
+                let mut names: Vec<_> = self
+                    .context
+                    .panes
+                    .iter()
+                    .filter(|pane| plugin.is_none_or(|plugin| pane.plugin == plugin))
+                    .flat_map(Pane::names)
+                    .filter(|name| abbreviates(prefix, name))
+                    .map(String::from)
+                    .collect();
+                names.sort();
+                names.dedup();
+                names
+            }
+            Type::Tab => self
+                .context
+                .tabs
+                .iter()
+                .filter(|name| abbreviates(prefix, name))
+                .cloned()
+                .collect(),
+            _ => return None,
+        };
+
+        Some(Completion {
+            span,
+            candidates,
+            selected: 0,
+        })
+    }
+
     pub(crate) fn selection_up(&mut self) {
-        match &mut self.action {
-            ActionList::HelpAll { selection }
+        if let Some(completion) = &mut self.completion {
+            let count = completion.candidates.len();
+            completion.selected = (completion.selected + count - 1) % count;
+            return;
+        }
+
+        match self.sequence.last_mut() {
+            ActionList::Focus {
+                panes, selected, ..
+            } if !panes.is_empty() => {
+                *selected = (*selected + panes.len() - 1) % panes.len();
+            }
+            ActionList::HelpAll { selection, .. }
             | ActionList::HelpPane { selection }
             | ActionList::HelpPipe { selection } => match selection {
                 Selection::One { row, max } => {
@@ -493,8 +1995,18 @@ impl Action {
     }
 
     pub(crate) fn selection_down(&mut self) {
-        match &mut self.action {
-            ActionList::HelpAll { selection }
+        if let Some(completion) = &mut self.completion {
+            completion.selected = (completion.selected + 1) % completion.candidates.len();
+            return;
+        }
+
+        match self.sequence.last_mut() {
+            ActionList::Focus {
+                panes, selected, ..
+            } if !panes.is_empty() => {
+                *selected = (*selected + 1) % panes.len();
+            }
+            ActionList::HelpAll { selection, .. }
             | ActionList::HelpPane { selection }
             | ActionList::HelpPipe { selection } => match selection {
                 Selection::One { row, max } => *row = (*row + 1) % *max,
@@ -505,11 +2017,134 @@ impl Action {
     }
 }
 
-impl Default for Action {
-    fn default() -> Self {
-        Action {
-            command: String::new(),
-            action: ActionList::Unknown,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(tokens: &[Token]) -> Vec<&str> {
+        tokens.iter().map(|token| token.value.as_str()).collect()
+    }
+
+    fn run_tokens(arguments: &str) -> Vec<Token> {
+        token::tokenize(arguments, &BTreeMap::new()).unwrap()
+    }
+
+    /// The files opened by `Edit <arguments>`, with their line, and the column
+    fn edit(arguments: &str) -> (Vec<(String, Option<usize>)>, Option<usize>) {
+        let tokens = run_tokens(&format!("Edit {arguments}"));
+        let action = ActionList::parse(
+            &tokens,
+            &Interface::Pane,
+            &Context::default(),
+            Validation::Deferred,
+            false,
+        );
+        let ActionList::Edit { files, column, .. } = action else {
+            panic!("`Edit {arguments}` gives {action:?}");
+        };
+        let files = files
+            .into_iter()
+            .map(|file| (file.path.display().to_string(), file.line_number))
+            .collect();
+        (files, column)
+    }
+
+    #[test]
+    fn values_are_those_of_the_arguments() {
+        for action in ActionList::iter() {
+            let names: Vec<_> = action.arguments().iter().map(|a| a.name).collect();
+            let values: Vec<_> = action.values().into_iter().map(|(name, _)| name).collect();
+            if !values.is_empty() {
+                assert_eq!(values, names, "{action:?}");
+            }
         }
     }
+
+    #[test]
+    fn positions_in_paths() {
+        let cases = [
+            ("a.rs", ("a.rs", None, None)),
+            ("a.rs:3", ("a.rs", Some(3), None)),
+            ("a.rs:3:7", ("a.rs", Some(3), Some(7))),
+            ("a.rs:3:7:", ("a.rs", Some(3), Some(7))),
+            ("a.rs:", ("a.rs", None, None)),
+            ("a:b.rs:3", ("a:b.rs", Some(3), None)),
+            ("a.rs:x", ("a.rs:x", None, None)),
+            ("a.rs:1:2:3", ("a.rs:1", Some(2), Some(3))),
+            (":3", (":3", None, None)),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(split_position(path), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn locations() {
+        let file = |path: &str, line| (path.to_string(), line);
+        let cases = [
+            ("a.rs", (vec![file("a.rs", None)], None)),
+            ("a.rs:3", (vec![file("a.rs", Some(3))], None)),
+            ("a.rs:3:7", (vec![file("a.rs", Some(3))], Some(7))),
+            ("+3 a.rs", (vec![file("a.rs", Some(3))], None)),
+            ("a.rs 3", (vec![file("a.rs", Some(3))], None)),
+            ("a.rs 3 7", (vec![file("a.rs", Some(3))], Some(7))),
+            ("a.rs --line 3", (vec![file("a.rs", Some(3))], None)),
+            (
+                "a.rs:3 b.rs:4 c.rs",
+                (
+                    vec![
+                        file("a.rs", Some(3)),
+                        file("b.rs", Some(4)),
+                        file("c.rs", None),
+                    ],
+                    None,
+                ),
+            ),
+            ("-- a.rs:3", (vec![file("a.rs:3", None)], None)),
+            (
+                "a.rs b.rs",
+                (vec![file("a.rs", None), file("b.rs", None)], None),
+            ),
+        ];
+        for (arguments, expected) in cases {
+            assert_eq!(edit(arguments), expected, "Edit {arguments}");
+        }
+    }
+
+    #[test]
+    fn assignments_before_the_program() {
+        let tokens = split_assignments(&run_tokens("A=1 B=2 cargo C=3"));
+        assert_eq!(
+            values(&tokens),
+            ["---env", "A=1", "---env", "B=2", "cargo", "C=3"]
+        );
+
+        let tokens = split_assignments(&run_tokens("--env-file .env FOO=1 ---cwd src cargo"));
+        assert_eq!(
+            values(&tokens),
+            [
+                "--env-file",
+                ".env",
+                "---env",
+                "FOO=1",
+                "---cwd",
+                "src",
+                "cargo"
+            ]
+        );
+
+        // After `--`, it is the program
+        assert!(leading_assignments(&run_tokens("-- A=1 cargo")).is_empty());
+        assert!(leading_assignments(&run_tokens("cargo A=1")).is_empty());
+    }
+
+    #[test]
+    fn assignments_are_bound_to_env() {
+        let tokens = split_assignments(&run_tokens("--env-file .env FOO=1 cargo test"));
+        let values = bind(RUN_ARGUMENTS, &tokens).unwrap();
+        assert_eq!(values.get_all("env"), ["FOO=1"]);
+        assert_eq!(values.get("env-file").as_deref(), Some(".env"));
+        assert_eq!(values.get("command").as_deref(), Some("cargo"));
+        assert_eq!(values.get_all("args"), ["test"]);
+    }
 }
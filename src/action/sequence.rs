@@ -0,0 +1,504 @@
+use std::ops::Range;
+
+use super::token::{quote, tokenize, Operator, Token};
+use super::{ActionList, Context, Interface, ParseError, Validation};
+
+/// When a step of a `Sequence` is executed, depending on the result of the previous steps
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) enum Condition {
+    /// First step, or after a `;`
+    #[default]
+    Always,
+    /// After a `&&`
+    OnSuccess,
+    /// After a `||`
+    OnFailure,
+    /// After a `|`: started with the previous step, it is given its output
+    Pipe,
+}
+
+impl Condition {
+    /// Whether a step with this condition runs, knowing if the last executed step succeeded
+    pub(crate) fn should_run(&self, last_succeeded: bool) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::OnSuccess | Condition::Pipe => last_succeeded,
+            Condition::OnFailure => !last_succeeded,
+        }
+    }
+
+    /// The operator written before a step with this condition
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Condition::Always => ";",
+            Condition::OnSuccess => "&&",
+            Condition::OnFailure => "||",
+            Condition::Pipe => "|",
+        }
+    }
+}
+
+impl From<Operator> for Condition {
+    fn from(operator: Operator) -> Self {
+        match operator {
+            Operator::Then => Condition::Always,
+            Operator::And => Condition::OnSuccess,
+            Operator::Or => Condition::OnFailure,
+            Operator::Pipe => Condition::Pipe,
+        }
+    }
+}
+
+/// One of the commands of a `Sequence`
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Step {
+    pub(crate) condition: Condition,
+    /// The part of the prompt this step was parsed from
+    pub(crate) command: String,
+    pub(crate) action: ActionList,
+    /// What this step was parsed from, to know if it can be reused after an edit
+    tokens: Vec<Token>,
+    validation: Validation,
+}
+
+/// Commands chained with `;`, `&&`, `||` and `|`, executed in order
+#[derive(Debug, Clone)]
+pub(crate) struct Sequence {
+    // Never empty: an empty prompt is a single `Unknown` step
+    steps: Vec<Step>,
+    /// The command once its variables are replaced, if it had any
+    expanded: Option<String>,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Sequence {
+            steps: vec![Step::default()],
+            expanded: None,
+        }
+    }
+}
+
+impl Sequence {
+    pub(crate) fn parse(command: &str, interface: &Interface, context: &Context) -> Self {
+        Sequence::from_tokens(
+            command,
+            tokenize(command, &context.variables),
+            interface,
+            context,
+            Validation::Full,
+            None,
+        )
+    }
+
+    /// Build the sequence from the `tokens` of the `command`.
+    /// The steps of the `previous` sequence whose tokens did not change are reused as is.
+    pub(crate) fn from_tokens(
+        command: &str,
+        tokens: Result<Vec<Token>, ParseError>,
+        interface: &Interface,
+        context: &Context,
+        validation: Validation,
+        previous: Option<&Sequence>,
+    ) -> Self {
+        let tokens = tokens.and_then(|tokens| expand_aliases(tokens, context));
+        let tokens = match tokens {
+            Ok(tokens) => tokens,
+            Err(error) => {
+                return Sequence {
+                    steps: vec![Step {
+                        condition: Condition::Always,
+                        command: command.to_string(),
+                        action: ActionList::Invalid { error },
+                        tokens: Vec::new(),
+                        validation,
+                    }],
+                    expanded: None,
+                }
+            }
+        };
+
+        let expanded = tokens.iter().any(|t| t.expanded).then(|| {
+            tokens
+                .iter()
+                .map(|t| match t.operator {
+                    Some(operator) => operator.as_str().to_string(),
+                    None => quote(&t.value),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+
+        let parse_step = |index: usize, condition: Condition, group: &[Token]| {
+            let reusable = previous
+                .and_then(|previous| previous.steps.get(index))
+                .filter(|step| {
+                    step.condition == condition
+                        && step.tokens == group
+                        && (step.validation == Validation::Full
+                            || validation == Validation::Deferred)
+                });
+            match reusable {
+                Some(step) => step.clone(),
+                None => Step::parse(condition, command, group, interface, context, validation),
+            }
+        };
+
+        let mut steps: Vec<Step> = Vec::new();
+        let mut condition = Condition::Always;
+        let mut operator_span = 0..0;
+        let mut group: Vec<Token> = Vec::new();
+        for token in tokens {
+            let Some(operator) = token.operator else {
+                group.push(token);
+                continue;
+            };
+
+            let step = if group.is_empty() {
+                Step {
+                    condition,
+                    command: String::new(),
+                    action: ActionList::Invalid {
+                        error: ParseError {
+                            message: format!("Missing a command before `{}`", operator.as_str()),
+                            span: token.span.clone(),
+                        },
+                    },
+                    tokens: Vec::new(),
+                    validation,
+                }
+            } else {
+                check_pipe(
+                    steps.last(),
+                    parse_step(steps.len(), condition, &group),
+                    &operator_span,
+                )
+            };
+            steps.push(step);
+
+            group.clear();
+            condition = operator.into();
+            operator_span = token.span.clone();
+        }
+        // The last step is the one being written, it is fine for it to still be empty
+        let step = parse_step(steps.len(), condition, &group);
+        steps.push(check_pipe(steps.last(), step, &operator_span));
+
+        Sequence { steps, expanded }
+    }
+
+    /// Whether the checks deferred while typing were done on every step
+    pub(crate) fn is_validated(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| step.validation == Validation::Full)
+    }
+
+    /// Unwrap the actions that are `Unavailable` on the calling interface
+    pub(crate) fn force(&mut self) {
+        for step in self.steps.iter_mut() {
+            if let ActionList::Unavailable { action, .. } = &step.action {
+                step.action = (**action).clone();
+            }
+        }
+    }
+
+    pub(crate) fn expanded(&self) -> Option<&str> {
+        self.expanded.as_deref()
+    }
+
+    /// Why the steps could not be read, if any
+    pub(crate) fn errors(&self) -> impl Iterator<Item = &ParseError> {
+        self.steps.iter().filter_map(|step| match &step.action {
+            ActionList::Invalid { error } => Some(error),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// The action of the step currently being written
+    pub(crate) fn last(&self) -> &ActionList {
+        &self.steps.last().expect("A sequence is never empty").action
+    }
+
+    /// The action of the step currently being written
+    pub(crate) fn last_mut(&mut self) -> &mut ActionList {
+        &mut self
+            .steps
+            .last_mut()
+            .expect("A sequence is never empty")
+            .action
+    }
+}
+
+impl Step {
+    /// The words of this step, the first one is the name of the action
+    pub(crate) fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    fn parse(
+        condition: Condition,
+        command: &str,
+        tokens: &[Token],
+        interface: &Interface,
+        context: &Context,
+        validation: Validation,
+    ) -> Self {
+        let command = match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => command[first.span.start..last.span.end].to_string(),
+            _ => String::new(),
+        };
+
+        Step {
+            condition,
+            command,
+            action: ActionList::parse(
+                tokens,
+                interface,
+                context,
+                validation,
+                condition == Condition::Pipe,
+            )
+            .restrict(interface),
+            tokens: tokens.to_vec(),
+            validation,
+        }
+    }
+}
+
+/// Only the output of `Run` can be given to `Edit` with a `|`, the `step` is `Invalid` otherwise
+fn check_pipe(previous: Option<&Step>, step: Step, operator_span: &Range<usize>) -> Step {
+    if step.condition != Condition::Pipe {
+        return step;
+    }
+
+    // The steps that are already wrong, or still being written, have their own error
+    let is_wrong = |action: &ActionList| {
+        matches!(
+            action,
+            ActionList::Unknown { .. }
+                | ActionList::Unavailable { .. }
+                | ActionList::Invalid { .. }
+                | ActionList::Ambiguous { .. }
+        )
+    };
+    let span = match (step.tokens.first(), step.tokens.last()) {
+        (Some(first), Some(last)) => first.span.start..last.span.end,
+        _ => operator_span.clone(),
+    };
+    let (message, span) = match (previous.map(|p| &p.action), &step.action) {
+        (Some(ActionList::Run { .. }), ActionList::Edit { files, .. }) if files.len() <= 1 => {
+            return step
+        }
+        (Some(ActionList::Run { .. }), ActionList::Edit { .. }) => {
+            ("The output of `Run` is written in a single file", span)
+        }
+        (Some(ActionList::Run { .. }), action) if is_wrong(action) => return step,
+        (Some(ActionList::Run { .. }), _) => {
+            ("The output of `Run` can only be given to `Edit`", span)
+        }
+        (Some(previous), _) if is_wrong(previous) => return step,
+        _ => (
+            "Only the output of `Run` can be given with `|`",
+            operator_span.clone(),
+        ),
+    };
+
+    Step {
+        action: ActionList::Invalid {
+            error: ParseError {
+                message: message.to_string(),
+                span,
+            },
+        },
+        ..step
+    }
+}
+
+/// Replace the commands starting with an alias by what it stands for
+fn expand_aliases(tokens: Vec<Token>, context: &Context) -> Result<Vec<Token>, ParseError> {
+    if context.aliases.is_empty() {
+        return Ok(tokens);
+    }
+
+    let mut expanded = Vec::with_capacity(tokens.len());
+    for group in tokens.split_inclusive(|t| t.operator.is_some()) {
+        let (words, operator) = match group.split_last() {
+            Some((last, words)) if last.operator.is_some() => (words, Some(last)),
+            _ => (group, None),
+        };
+
+        let alias = words.split_first().and_then(|(name, arguments)| {
+            let alias = context.aliases.get(&name.value.to_lowercase());
+            alias
+                .filter(|_| !name.literal)
+                .map(|alias| (name, alias, arguments))
+        });
+
+        if let Some((name, alias, arguments)) = alias {
+            let span = name.span.start..words.last().unwrap_or(name).span.end;
+            let alias_tokens = tokenize(alias, &context.variables).map_err(|error| ParseError {
+                message: format!("In the alias `{}`: {}", name.value, error.message),
+                span: span.clone(),
+            })?;
+            let alias_tokens = substitute_parameters(alias_tokens, arguments);
+
+            // Point to the alias in the prompt since this is what the user wrote
+            expanded.extend(alias_tokens.into_iter().map(|token| Token {
+                span: span.clone(),
+                expanded: true,
+                ..token
+            }));
+        } else {
+            expanded.extend(words.iter().cloned());
+        }
+        expanded.extend(operator.cloned());
+    }
+
+    Ok(expanded)
+}
+
+/// Replace `$1` to `$9` in the words of an alias by the corresponding argument and `$@` by all of them.
+/// A word that is only a `$N` becomes the argument as is, whatever its quotes and spaces,
+/// and is left out when the argument is missing so the action can tell it is.
+/// If the alias uses none of them, the arguments are added at its end.
+fn substitute_parameters(alias: Vec<Token>, arguments: &[Token]) -> Vec<Token> {
+    let mut result = Vec::with_capacity(alias.len() + arguments.len());
+    let mut used = false;
+
+    for token in alias {
+        if token.operator.is_some() {
+            result.push(token);
+            continue;
+        }
+        match token.value.as_str() {
+            "$@" => {
+                used = true;
+                result.extend(arguments.iter().cloned());
+            }
+            value if parameter_index(value).is_some() => {
+                used = true;
+                result.extend(
+                    parameter_index(value)
+                        .and_then(|i| arguments.get(i))
+                        .cloned(),
+                );
+            }
+            value => {
+                let (value, replaced) = substitute_in_word(value, arguments);
+                used |= replaced;
+                result.push(Token { value, ..token });
+            }
+        }
+    }
+
+    if !used {
+        result.extend(arguments.iter().cloned());
+    }
+
+    result
+}
+
+/// The index of the argument a `$1` to `$9` stands for
+fn parameter_index(value: &str) -> Option<usize> {
+    match value.as_bytes() {
+        [b'$', digit @ b'1'..=b'9'] => Some((digit - b'1') as usize),
+        _ => None,
+    }
+}
+
+/// Replace the parameters inside a word: `--name=$1`. The missing arguments are empty.
+fn substitute_in_word(word: &str, arguments: &[Token]) -> (String, bool) {
+    let mut result = String::with_capacity(word.len());
+    let mut used = false;
+
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('$', Some('@')) => {
+                chars.next();
+                used = true;
+                let all: Vec<_> = arguments.iter().map(|a| a.value.as_str()).collect();
+                result.push_str(&all.join(" "));
+            }
+            ('$', Some(digit @ '1'..='9')) => {
+                let index = *digit as usize - '1' as usize;
+                chars.next();
+                used = true;
+                result.push_str(arguments.get(index).map_or("", |a| a.value.as_str()));
+            }
+            _ => result.push(c),
+        }
+    }
+
+    (result, used)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn substitute(alias: &str, arguments: &str) -> Vec<String> {
+        let alias = tokenize(alias, &BTreeMap::new()).unwrap();
+        let arguments = tokenize(arguments, &BTreeMap::new()).unwrap();
+        substitute_parameters(alias, &arguments)
+            .into_iter()
+            .map(|token| token.value)
+            .collect()
+    }
+
+    #[test]
+    fn arguments_keep_their_quotes() {
+        assert_eq!(
+            substitute("Run git commit -m '$1'", "'fix the bug'"),
+            ["Run", "git", "commit", "-m", "fix the bug"]
+        );
+        assert_eq!(
+            substitute("Run echo $1", r#""it's""#),
+            ["Run", "echo", "it's"]
+        );
+        assert_eq!(substitute("Run echo \"$1\"", "a"), ["Run", "echo", "a"]);
+    }
+
+    #[test]
+    fn missing_arguments_are_left_out() {
+        assert_eq!(
+            substitute("Edit $1 ---cwd /x", ""),
+            ["Edit", "---cwd", "/x"]
+        );
+        assert_eq!(substitute("Run diff $1 $2", "a"), ["Run", "diff", "a"]);
+    }
+
+    #[test]
+    fn all_arguments() {
+        assert_eq!(
+            substitute("Run cargo $@ ---cwd /x", "test 'a b'"),
+            ["Run", "cargo", "test", "a b", "---cwd", "/x"]
+        );
+        assert_eq!(substitute("Run cargo $@", ""), ["Run", "cargo"]);
+    }
+
+    #[test]
+    fn arguments_inside_a_word() {
+        assert_eq!(
+            substitute("Run echo --name=$1 $2-$1", "'a b' c"),
+            ["Run", "echo", "--name=a b", "c-a b"]
+        );
+        assert_eq!(substitute("Run echo x$2", "a"), ["Run", "echo", "x"]);
+    }
+
+    #[test]
+    fn unused_arguments_are_added_at_the_end() {
+        assert_eq!(
+            substitute("Run cargo test ; NewTab", "x"),
+            ["Run", "cargo", "test", ";", "NewTab", "x"]
+        );
+        assert_eq!(substitute("Run cargo", ""), ["Run", "cargo"]);
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::ops::Range;
+
+use super::ParseError;
+
+/// A word of the prompt, once the quotes and escapes have been resolved
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Token {
+    pub(crate) value: String,
+    /// Byte range of the word in the typed command (quotes included)
+    pub(crate) span: Range<usize>,
+    /// The word comes after a `--`: it must never be understood as a flag
+    pub(crate) literal: bool,
+    /// The word is the `--` ending the options, it is consumed by `argument::bind`
+    pub(crate) ends_options: bool,
+    /// The word is not a value but a separator between 2 commands
+    pub(crate) operator: Option<Operator>,
+    /// At least one variable was replaced by its value in this word
+    pub(crate) expanded: bool,
+}
+
+/// Separator between 2 commands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Operator {
+    /// `;`: run the next command whatever happens
+    Then,
+    /// `&&`: run the next command if the previous one succeeded
+    And,
+    /// `||`: run the next command if the previous one failed
+    Or,
+    /// `|`: give the output of the previous command to the next one
+    Pipe,
+}
+
+impl Operator {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Then => ";",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Pipe => "|",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quote {
+    Single,
+    Double,
+}
+
+/// Where the tokenizer was between 2 words, to resume from there when only the end of the command changes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Checkpoint {
+    /// Byte position in the command, everything before it is already split into words
+    pub(crate) position: usize,
+    /// How many tokens come before `position`
+    tokens: usize,
+    options_ended: bool,
+}
+
+/// Split a command into words following the usual shell rules:
+/// - `'single quotes'` keep everything as is
+/// - `"double quotes"` keep everything except `\"`, `\\`, `\$` and `` \` `` which are unescaped
+/// - `\` outside of quotes escapes the next character
+/// - a lone `--` ends the options: every following word is `literal`. It is kept, marked as `ends_options`.
+/// - `;`, `&&`, `||` and `|` separate commands, they are returned as `operator` tokens
+/// - `$NAME` and `${NAME}` are replaced by the value of the variable, except in single quotes.
+///   Unknown variables are kept as is and values are never split into several words.
+pub(crate) fn tokenize(
+    command: &str,
+    variables: &BTreeMap<String, String>,
+) -> Result<Vec<Token>, ParseError> {
+    tokenize_from(command, variables, &[], Checkpoint::default()).map(|(tokens, _)| tokens)
+}
+
+/// Same as `tokenize`, resuming from the `checkpoint` of a previous command starting the same way,
+/// whose tokens are in `previous`. The last checkpoint reached is returned with the tokens.
+pub(crate) fn tokenize_from(
+    command: &str,
+    variables: &BTreeMap<String, String>,
+    previous: &[Token],
+    checkpoint: Checkpoint,
+) -> Result<(Vec<Token>, Checkpoint), ParseError> {
+    let mut tokens = previous[..checkpoint.tokens].to_vec();
+    let mut options_ended = checkpoint.options_ended;
+    let mut checkpoint = checkpoint;
+    let mut expanded = false;
+
+    let mut value = String::new();
+    // Start of the current word, `None` while we are between words
+    let mut start: Option<usize> = None;
+    // Set when the current word contains quotes, so `""` is an empty word and `"--"` is not the end of options
+    let mut quoted = false;
+    let mut quote: Option<(Quote, usize)> = None;
+
+    let resume = checkpoint.position;
+    let mut chars = command[resume..]
+        .char_indices()
+        .map(|(i, c)| (resume + i, c))
+        .peekable();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some((Quote::Single, _)) => {
+                if c == '\'' {
+                    quote = None;
+                } else {
+                    value.push(c);
+                }
+            }
+            Some((Quote::Double, _)) => match c {
+                '"' => quote = None,
+                '\\' => match chars.peek() {
+                    Some((_, next @ ('"' | '\\' | '$' | '`'))) => {
+                        value.push(*next);
+                        chars.next();
+                    }
+                    _ => value.push(c),
+                },
+                '$' => expanded |= expand_variable(&mut chars, &mut value, variables),
+                _ => value.push(c),
+            },
+            None => match c {
+                _ if c.is_whitespace() => {
+                    if let Some(s) = start.take() {
+                        push_token(
+                            &mut tokens,
+                            &mut options_ended,
+                            std::mem::take(&mut value),
+                            s..i,
+                            quoted,
+                            std::mem::take(&mut expanded),
+                        );
+                        quoted = false;
+                    }
+                    checkpoint = Checkpoint {
+                        position: i + c.len_utf8(),
+                        tokens: tokens.len(),
+                        options_ended,
+                    };
+                }
+                '$' => {
+                    start.get_or_insert(i);
+                    expanded |= expand_variable(&mut chars, &mut value, variables);
+                }
+                '\'' | '"' => {
+                    start.get_or_insert(i);
+                    quoted = true;
+                    let kind = if c == '\'' {
+                        Quote::Single
+                    } else {
+                        Quote::Double
+                    };
+                    quote = Some((kind, i));
+                }
+                ';' | '|' | '&' if c != '&' || chars.peek().is_some_and(|(_, n)| *n == c) => {
+                    if let Some(s) = start.take() {
+                        push_token(
+                            &mut tokens,
+                            &mut options_ended,
+                            std::mem::take(&mut value),
+                            s..i,
+                            quoted,
+                            std::mem::take(&mut expanded),
+                        );
+                        quoted = false;
+                    }
+
+                    let (operator, end) = match c {
+                        ';' => (Operator::Then, i + 1),
+                        '&' => (Operator::And, i + 2),
+                        _ if chars.peek().is_some_and(|(_, n)| *n == '|') => (Operator::Or, i + 2),
+                        _ => (Operator::Pipe, i + 1),
+                    };
+                    if end == i + 2 {
+                        chars.next();
+                    }
+                    // Options can be given again to the next command
+                    options_ended = false;
+                    tokens.push(Token {
+                        value: operator.as_str().to_string(),
+                        span: i..end,
+                        literal: false,
+                        ends_options: false,
+                        operator: Some(operator),
+                        expanded: false,
+                    });
+                    // A `|` at the end may still become a `||`
+                    if operator != Operator::Pipe || chars.peek().is_some() {
+                        checkpoint = Checkpoint {
+                            position: end,
+                            tokens: tokens.len(),
+                            options_ended,
+                        };
+                    }
+                }
+                '\\' => {
+                    start.get_or_insert(i);
+                    match chars.next() {
+                        Some((_, next)) => value.push(next),
+                        None => {
+                            return Err(ParseError {
+                                message: String::from("Nothing to escape after the final `\\`"),
+                                span: i..command.len(),
+                            })
+                        }
+                    }
+                }
+                _ => {
+                    start.get_or_insert(i);
+                    value.push(c);
+                }
+            },
+        }
+    }
+
+    if let Some((kind, position)) = quote {
+        let name = match kind {
+            Quote::Single => "single",
+            Quote::Double => "double",
+        };
+        return Err(ParseError {
+            message: format!("Unterminated {name} quote"),
+            span: position..command.len(),
+        });
+    }
+
+    if let Some(s) = start {
+        push_token(
+            &mut tokens,
+            &mut options_ended,
+            value,
+            s..command.len(),
+            quoted,
+            expanded,
+        );
+    }
+
+    Ok((tokens, checkpoint))
+}
+
+fn push_token(
+    tokens: &mut Vec<Token>,
+    options_ended: &mut bool,
+    value: String,
+    span: Range<usize>,
+    quoted: bool,
+    expanded: bool,
+) {
+    let ends_options = !*options_ended && !quoted && value == "--";
+    tokens.push(Token {
+        value,
+        span,
+        literal: *options_ended,
+        ends_options,
+        operator: None,
+        expanded,
+    });
+    *options_ended |= ends_options;
+}
+
+/// Replace the variable whose name follows a `$`, return whether it was known
+fn expand_variable(
+    chars: &mut Peekable<impl Iterator<Item = (usize, char)>>,
+    value: &mut String,
+    variables: &BTreeMap<String, String>,
+) -> bool {
+    let braced = chars.next_if(|(_, c)| *c == '{').is_some();
+    let mut name = String::new();
+    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+        name.push(c);
+    }
+    let closed = braced && chars.next_if(|(_, c)| *c == '}').is_some();
+
+    match variables.get(&name) {
+        Some(variable) if closed || !braced => {
+            value.push_str(variable);
+            true
+        }
+        _ => {
+            value.push('$');
+            if braced {
+                value.push('{');
+            }
+            value.push_str(&name);
+            if closed {
+                value.push('}');
+            }
+            false
+        }
+    }
+}
+
+/// Quote a value so `tokenize` reads it back as a single word, a `--` included.
+/// A `~` is quoted too, so a shell does not expand it either.
+pub(crate) fn quote(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value != "--"
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./:=+,@%".contains(c));
+
+    if is_plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r#"'"'"'"#))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(command: &str) -> Vec<String> {
+        let variables = BTreeMap::from([("NAME".to_string(), "a b".to_string())]);
+        tokenize(command, &variables)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.value)
+            .collect()
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(
+            values(r#"Run 'a b' "c d" e"f"'g'"#),
+            ["Run", "a b", "c d", "efg"]
+        );
+        assert_eq!(values(r#"'' "" x"#), ["", "", "x"]);
+        assert_eq!(
+            values(r#"'$NAME' "$NAME" ${NAME}x $OTHER"#),
+            ["$NAME", "a b", "a bx", "$OTHER"]
+        );
+
+        let error = tokenize("Run 'a b", &BTreeMap::new()).unwrap_err();
+        assert_eq!(error.message, "Unterminated single quote");
+        assert_eq!(error.span, 4..8);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(values(r"a\ b \; \'"), ["a b", ";", "'"]);
+        assert_eq!(values(r#""\" \\ \$ \n""#), [r#"" \ $ \n"#]);
+        assert_eq!(values(r"'\n'"), [r"\n"]);
+        assert!(tokenize(r"Run \", &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn end_of_options() {
+        let tokens = tokenize("Run -- --x -- '--'", &BTreeMap::new()).unwrap();
+        let flags: Vec<_> = tokens.iter().map(|t| (t.literal, t.ends_options)).collect();
+        assert_eq!(
+            flags,
+            [
+                (false, false),
+                (false, true),
+                (true, false),
+                (true, false),
+                (true, false)
+            ]
+        );
+
+        // A quoted `--` is a value, and the next command can have options again
+        let tokens = tokenize("Edit '--' ; Run -- x", &BTreeMap::new()).unwrap();
+        assert!(!tokens[1].ends_options && !tokens[1].literal);
+        assert!(tokens[4].ends_options && !tokens[4].literal);
+        assert!(tokens[5].literal);
+    }
+
+    #[test]
+    fn operators() {
+        let tokens = tokenize("a;b && c||d | e & f", &BTreeMap::new()).unwrap();
+        let operators: Vec<_> = tokens.iter().filter_map(|t| t.operator).collect();
+        assert_eq!(
+            operators,
+            [Operator::Then, Operator::And, Operator::Or, Operator::Pipe]
+        );
+        assert_eq!(
+            values("a;b && c||d | e & f"),
+            ["a", ";", "b", "&&", "c", "||", "d", "|", "e", "&", "f"]
+        );
+        assert_eq!(tokens[3].span, 4..6);
+        assert_eq!(values("'a;b' \"c|d\""), ["a;b", "c|d"]);
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let variables = BTreeMap::new();
+        let typed = [
+            "Run -- car",
+            "Run -- cargo t",
+            "Run -- cargo test |",
+            "Run -- cargo test || Edit",
+        ];
+        let (mut tokens, mut checkpoint) =
+            tokenize_from(typed[0], &variables, &[], Checkpoint::default()).unwrap();
+        for command in &typed[1..] {
+            (tokens, checkpoint) = tokenize_from(command, &variables, &tokens, checkpoint).unwrap();
+            assert_eq!(tokens, tokenize(command, &variables).unwrap());
+        }
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        for value in [
+            "plain/path.rs",
+            "a b",
+            "it's",
+            "",
+            "$NAME",
+            r#"a"b"#,
+            r"\",
+            "x;y",
+            "--",
+            "~/x",
+        ] {
+            let tokens = tokenize(&quote(value), &BTreeMap::new()).unwrap();
+            assert_eq!(tokens.len(), 1, "{value}");
+            assert_eq!(tokens[0].value, value);
+            assert!(!tokens[0].ends_options);
+        }
+        assert_eq!(quote("src/main.rs"), "src/main.rs");
+        assert_eq!(quote("~/x"), "'~/x'");
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use kdl::KdlDocument;
+use strum::EnumMessage;
+use zellij_tile::prelude::{CharOrArrow, Key};
+
+use crate::action::host::HOST_FOLDER;
+use crate::action::ActionList;
+use crate::shell::Shell;
+
+/// Prefix of the configuration keys defining an alias: `alias_t "Run cargo test"`
+const ALIAS_PREFIX: &str = "alias_";
+/// Prefix of the configuration keys defining a macro, one command per line
+const MACRO_PREFIX: &str = "macro_";
+/// Configuration key of a KDL file, relative to the host folder, defining more macros
+const MACROS_FILE: &str = "macros_file";
+/// Configuration key of the shell running the commands with the shell’s environment, instead of the user’s `$SHELL`
+const SHELL: &str = "shell";
+/// Configuration key telling if that shell is started as a login shell: `true` or `false`
+const SHELL_LOGIN: &str = "shell_login";
+/// Configuration key telling if that shell is started as an interactive shell: `true` or `false`
+const SHELL_INTERACTIVE: &str = "shell_interactive";
+/// Prefix of the configuration keys binding a control, to one or more keys: `key_toggle_floating "Alt f, Ctrl o"`
+const KEY_PREFIX: &str = "key_";
+
+/// The keys of the controls of the plugin pane
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Keybindings {
+    /// Write the rest of the suggested command
+    pub(crate) accept_autosuggestion: Vec<Key>,
+    /// Open the new panes floating or tiled
+    pub(crate) toggle_floating: Vec<Key>,
+    /// Run the commands with the environment of Zellij or of the user’s shell
+    pub(crate) toggle_environment: Vec<Key>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        // Like in fish
+        Keybindings {
+            accept_autosuggestion: vec![Key::Right, Key::Ctrl('f')],
+            toggle_floating: vec![Key::Alt(CharOrArrow::Char('f'))],
+            toggle_environment: vec![Key::Ctrl('e')],
+        }
+    }
+}
+
+/// The user’s settings, read from the plugin configuration
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    /// Name of the alias (lowercase) and the command it stands for
+    pub(crate) aliases: BTreeMap<String, String>,
+    /// Name of the macro and the commands it runs, in order
+    pub(crate) macros: BTreeMap<String, Vec<String>>,
+    pub(crate) keys: Keybindings,
+    pub(crate) shell: Shell,
+    /// Settings that were ignored, with the reason why
+    pub(crate) errors: Vec<String>,
+}
+
+impl Config {
+    pub(crate) fn new(configuration: &BTreeMap<String, String>) -> Self {
+        let mut config = Config::default();
+
+        for (key, value) in configuration {
+            if let Some(name) = key.strip_prefix(ALIAS_PREFIX) {
+                config.add_alias(name, value);
+            } else if let Some(name) = key.strip_prefix(MACRO_PREFIX) {
+                let steps = value
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect();
+                config.add_macro(name, steps);
+            } else if let Some(control) = key.strip_prefix(KEY_PREFIX) {
+                config.bind_keys(control, value);
+            }
+        }
+
+        if let Some(file) = configuration.get(MACROS_FILE) {
+            config.read_macros_file(&Path::new(HOST_FOLDER).join(file));
+        }
+
+        if let Some(shell) = configuration.get(SHELL) {
+            config.shell.path = Some(PathBuf::from(shell));
+        }
+        if let Some(login) = config.read_switch(configuration, SHELL_LOGIN) {
+            config.shell.login = login;
+        }
+        if let Some(interactive) = config.read_switch(configuration, SHELL_INTERACTIVE) {
+            config.shell.interactive = interactive;
+        }
+
+        config
+    }
+
+    fn add_alias(&mut self, name: &str, command: &str) {
+        let name = name.to_lowercase();
+
+        if name.is_empty() {
+            self.errors.push(format!(
+                "The alias `{command}` has no name after `{ALIAS_PREFIX}`"
+            ));
+            return;
+        }
+
+        let shadowed = ActionList::filter_any().find(|action| {
+            action
+                .get_serializations()
+                .iter()
+                .any(|s| s.to_lowercase() == name)
+        });
+        if let Some(action) = shadowed {
+            let builtin = action
+                .get_serializations()
+                .first()
+                .expect("At least one serialization is guaranteed");
+            self.errors.push(format!(
+                "The alias `{name}` is ignored: it has the same name as the `{builtin}` action"
+            ));
+            return;
+        }
+
+        self.aliases.insert(name, command.to_string());
+    }
+
+    fn bind_keys(&mut self, control: &str, value: &str) {
+        let keys: Result<Vec<Key>, _> = value
+            .split(',')
+            .map(|key| key.trim().parse::<Key>())
+            .collect();
+        let keys = match keys {
+            Ok(keys) => keys,
+            Err(error) => {
+                self.errors.push(format!(
+                    "The keys of `{KEY_PREFIX}{control}` are ignored: {error}"
+                ));
+                return;
+            }
+        };
+
+        match control {
+            "accept_autosuggestion" => self.keys.accept_autosuggestion = keys,
+            "toggle_floating" => self.keys.toggle_floating = keys,
+            "toggle_environment" => self.keys.toggle_environment = keys,
+            _ => self.errors.push(format!(
+                "Unknown control `{control}`, the controls are `accept_autosuggestion`, `toggle_floating` and `toggle_environment`"
+            )),
+        }
+    }
+
+    fn read_switch(&mut self, configuration: &BTreeMap<String, String>, key: &str) -> Option<bool> {
+        let value = configuration.get(key)?;
+        match value.trim().parse() {
+            Ok(switch) => Some(switch),
+            Err(_) => {
+                self.errors.push(format!(
+                    "`{key}` is ignored: it is either `true` or `false`, not `{value}`"
+                ));
+                None
+            }
+        }
+    }
+
+    fn add_macro(&mut self, name: &str, steps: Vec<String>) {
+        if name.is_empty() {
+            self.errors
+                .push(format!("A macro has no name after `{MACRO_PREFIX}`"));
+        } else if steps.is_empty() {
+            self.errors
+                .push(format!("The macro `{name}` is ignored: it has no command"));
+        } else {
+            self.macros.insert(name.to_string(), steps);
+        }
+    }
+
+    /// Each node of the file is a macro, its arguments are the commands to run:
+    /// ```kdl
+    /// dev "NewPane" "Run cargo watch" "Edit src/main.rs"
+    /// ```
+    fn read_macros_file(&mut self, path: &Path) {
+        let document = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| text.parse::<KdlDocument>().map_err(|e| e.to_string()));
+        let document = match document {
+            Ok(document) => document,
+            Err(error) => {
+                self.errors.push(format!(
+                    "Cannot read the macros in `{}`: {error}",
+                    path.display()
+                ));
+                return;
+            }
+        };
+
+        for node in document.nodes() {
+            let steps = node
+                .entries()
+                .iter()
+                .filter(|entry| entry.name().is_none())
+                .filter_map(|entry| entry.value().as_string())
+                .map(String::from)
+                .collect();
+            self.add_macro(node.name().value(), steps);
+        }
+    }
+}
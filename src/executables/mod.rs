@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use zellij_tile::prelude::run_command;
+
+use crate::action::host::CACHE_FOLDER;
+use crate::action::token::quote;
+use crate::shell::Shell;
+use crate::EnvironmentFrom;
+
+/// Marks the `run_command` results that are lists of executables
+pub(crate) const SOURCE: &str = "executables";
+
+/// Print the name of every executable file in the folders of the `$PATH`
+const LIST_EXECUTABLES: &str = r#"
+IFS=:
+for folder in $PATH; do
+    for file in "$folder"/*; do
+        [ -f "$file" ] && [ -x "$file" ] && echo "${file##*/}"
+    done
+done
+true
+"#;
+
+/// Look for the executables of the `environment` in the background, the result comes in a `RunCommandResult` event.
+/// The `$PATH` of `EnvironmentFrom::DefaultShell` is the one the `shell` gets when it runs the commands.
+pub(crate) fn scan(environment: &EnvironmentFrom, shell: &Shell) {
+    let script = match environment {
+        EnvironmentFrom::ZellijSession => LIST_EXECUTABLES.to_string(),
+        // The profile and rc files often add folders to the `$PATH`, and may print things before it
+        EnvironmentFrom::DefaultShell => {
+            let (path, args) = shell.command(&["printenv".to_string(), "PATH".to_string()]);
+            let printenv = std::iter::once(path.to_string_lossy().to_string())
+                .chain(args)
+                .map(|word| quote(&word))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!(r#"PATH=$({printenv} | tail -n 1){LIST_EXECUTABLES}"#)
+        }
+    };
+    let context = BTreeMap::from([
+        ("source".to_string(), SOURCE.to_string()),
+        ("environment".to_string(), environment.as_str().to_string()),
+    ]);
+
+    run_command(&["sh", "-c", &script], context);
+}
+
+/// The sorted names printed by the `scan`, without duplicates
+pub(crate) fn parse(stdout: &[u8]) -> Vec<String> {
+    let mut executables: Vec<_> = String::from_utf8_lossy(stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+    executables.sort();
+    executables.dedup();
+
+    executables
+}
+
+/// The executables found by the last `scan` of the `environment`, even in a previous session
+pub(crate) fn read_cache(environment: &EnvironmentFrom) -> Option<Vec<String>> {
+    std::fs::read(cache_file(environment))
+        .ok()
+        .map(|content| parse(&content))
+}
+
+pub(crate) fn write_cache(environment: &EnvironmentFrom, executables: &[String]) {
+    if let Err(error) = std::fs::write(cache_file(environment), executables.join("\n")) {
+        eprintln!("Cannot cache the executables: {error}");
+    }
+}
+
+fn cache_file(environment: &EnvironmentFrom) -> PathBuf {
+    PathBuf::from(CACHE_FOLDER).join(format!("executables_{}", environment.as_str()))
+}
@@ -0,0 +1,87 @@
+use zellij_tile::prelude::{PaneInfo, PaneManifest};
+
+/// How many panes are remembered
+const MAX_ENTRIES: usize = 20;
+
+/// A pane, by its kind and its id
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PaneRef {
+    pub(crate) id: u32,
+    pub(crate) plugin: bool,
+}
+
+impl From<&PaneInfo> for PaneRef {
+    fn from(info: &PaneInfo) -> Self {
+        PaneRef {
+            id: info.id,
+            plugin: info.is_plugin,
+        }
+    }
+}
+
+/// The panes that were focused, the last one first.
+///
+/// Zellij runs one instance of the plugin for each client, and tells it which tab this client is in,
+/// so this history only follows the focus of its own client.
+/// The client ids are never given to the plugins, so the panes focused by the other clients in the same tab
+/// cannot be told apart: the pane that just got the focus is the one remembered.
+#[derive(Debug, Default)]
+pub(crate) struct FocusHistory {
+    list: Vec<PaneRef>,
+    /// The panes that had the focus in the active tab at the last update
+    focused: Vec<PaneRef>,
+    /// This plugin pane is never a target, the user just wrote a command in it
+    pub(crate) plugin_id: u32,
+}
+
+impl FocusHistory {
+    /// Remember the pane focused in the tab at `tab_position`, and forget the panes that were closed
+    pub(crate) fn update(&mut self, tab_position: Option<usize>, panes: &PaneManifest) {
+        let exists = |pane: &PaneRef| {
+            panes
+                .panes
+                .values()
+                .flatten()
+                .any(|info| info.id == pane.id && info.is_plugin == pane.plugin)
+        };
+        self.list.retain(exists);
+
+        // Plugin panes can be focused too, only the tab and status bars cannot
+        let focused: Vec<PaneRef> = tab_position
+            .and_then(|position| panes.panes.get(&position))
+            .into_iter()
+            .flatten()
+            .filter(|info| info.is_focused && info.is_selectable && !info.is_suppressed)
+            .map(PaneRef::from)
+            .collect();
+        let current = focused
+            .iter()
+            .find(|pane| !self.focused.contains(pane))
+            .or_else(|| focused.iter().find(|pane| self.list.first() == Some(*pane)))
+            .or(focused.first())
+            .copied();
+        self.focused = focused;
+
+        if let Some(focused) = current {
+            self.list.retain(|pane| *pane != focused);
+            self.list.insert(0, focused);
+            self.list.truncate(MAX_ENTRIES);
+        }
+    }
+
+    /// The last focused pane that is not this plugin
+    pub(crate) fn previous(&self) -> Option<PaneRef> {
+        self.list
+            .iter()
+            .copied()
+            .find(|pane| !(pane.plugin && pane.id == self.plugin_id))
+    }
+
+    /// The last focused terminal pane
+    pub(crate) fn previous_terminal(&self) -> Option<u32> {
+        self.list
+            .iter()
+            .find(|pane| !pane.plugin)
+            .map(|pane| pane.id)
+    }
+}
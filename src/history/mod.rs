@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use crate::action::host::CACHE_FOLDER;
+
+/// How many commands are remembered
+const MAX_ENTRIES: usize = 500;
+
+/// The commands run from the plugin pane, kept between sessions
+#[derive(Debug, Default)]
+pub(crate) struct History {
+    /// The most recent last, without duplicates
+    entries: Vec<String>,
+}
+
+impl History {
+    pub(crate) fn load() -> Self {
+        let entries = std::fs::read_to_string(history_file())
+            .map(|content| content.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        History { entries }
+    }
+
+    pub(crate) fn push(&mut self, command: &str) {
+        // A command from a pipe can span several lines, they would be read back as several commands
+        if command.trim().is_empty() || command.contains('\n') {
+            return;
+        }
+
+        self.entries.retain(|entry| entry != command);
+        self.entries.push(command.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+
+        if let Err(error) = std::fs::write(history_file(), self.entries.join("\n")) {
+            eprintln!("Cannot save the history: {error}");
+        }
+    }
+
+    /// The most recent command starting with `prefix` and longer than it
+    pub(crate) fn suggestion(&self, prefix: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.len() > prefix.len() && entry.starts_with(prefix))
+            .map(String::as_str)
+    }
+}
+
+fn history_file() -> PathBuf {
+    PathBuf::from(CACHE_FOLDER).join("history")
+}
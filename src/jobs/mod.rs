@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use zellij_tile::prelude::{run_command, run_command_with_env_variables_and_cwd, FileToOpen};
+
+/// Marks the `run_command` results that are the end of a job
+pub(crate) const SOURCE: &str = "job";
+/// Marks the `run_command` result that is the folder of the jobs
+pub(crate) const FOLDER_SOURCE: &str = "jobs-folder";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Status {
+    Running,
+    Exited(i32),
+    /// Ended by a signal, usually by `KillJob`
+    Killed,
+}
+
+/// A command run in the background, without a pane
+#[derive(Debug, Clone)]
+pub(crate) struct Job {
+    pub(crate) id: usize,
+    /// The command as shown to the user
+    pub(crate) command: String,
+    pub(crate) status: Status,
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+    /// Where the output is written instead of being kept, opened once the job is done: `Run … | Edit`
+    pub(crate) edit: Option<FileToOpen>,
+    /// Where the process id is written, so the job can be killed
+    pid_file: PathBuf,
+}
+
+impl Job {
+    /// The lines written by the command, its errors last
+    pub(crate) fn output(&self) -> Vec<&str> {
+        self.stdout.lines().chain(self.stderr.lines()).collect()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Jobs {
+    /// The oldest first
+    pub(crate) list: Vec<Job>,
+    last_id: usize,
+    /// Index in `list` of the job whose output is shown
+    pub(crate) selected: usize,
+    /// First line of the output shown
+    pub(crate) scroll: usize,
+    /// Folder of the host only this plugin writes in, created with `mktemp`.
+    /// The process id of each job is written there, and the output of `Run … | Edit` when no file is given.
+    pub(crate) folder: Option<PathBuf>,
+}
+
+impl Jobs {
+    /// Run the `program` in the background, return the id of its job
+    pub(crate) fn start(
+        &mut self,
+        command: String,
+        program: &[String],
+        env: BTreeMap<String, String>,
+        cwd: PathBuf,
+        edit: Option<FileToOpen>,
+    ) -> Result<usize, String> {
+        let pid_file = self.folder()?.join(format!("{}.pid", self.last_id + 1));
+        self.last_id += 1;
+        let id = self.last_id;
+
+        // Zellij cannot kill the commands it runs for plugins: the shell writes its pid then becomes the program
+        let pid_path = pid_file.to_string_lossy().to_string();
+        let output = edit
+            .as_ref()
+            .map(|file| file.path.to_string_lossy().to_string());
+        let mut words = match &output {
+            Some(output) => vec![
+                "sh",
+                "-c",
+                r#"echo $$ > "$0" && out="$1" && shift && exec "$@" > "$out""#,
+                &pid_path,
+                output,
+            ],
+            None => vec!["sh", "-c", r#"echo $$ > "$0" && exec "$@""#, &pid_path],
+        };
+        words.extend(program.iter().map(String::as_str));
+        let context = BTreeMap::from([
+            ("source".to_string(), SOURCE.to_string()),
+            ("id".to_string(), id.to_string()),
+        ]);
+        run_command_with_env_variables_and_cwd(&words, env, cwd, context);
+
+        self.list.push(Job {
+            id,
+            command,
+            status: Status::Running,
+            stdout: String::new(),
+            stderr: String::new(),
+            edit,
+            pid_file,
+        });
+        self.select(self.list.len() - 1);
+
+        Ok(id)
+    }
+
+    /// Keep the result of the job the `context` belongs to, if it is one of ours
+    pub(crate) fn finish(
+        &mut self,
+        context: &BTreeMap<String, String>,
+        exit_code: Option<i32>,
+        stdout: &[u8],
+        stderr: &[u8],
+    ) -> Option<Job> {
+        let id = context.get("id").and_then(|id| id.parse::<usize>().ok());
+        let job = self.list.iter_mut().find(|job| Some(job.id) == id)?;
+
+        job.status = match exit_code {
+            Some(code) => Status::Exited(code),
+            None => Status::Killed,
+        };
+        job.stdout = String::from_utf8_lossy(stdout).to_string();
+        job.stderr = String::from_utf8_lossy(stderr).to_string();
+
+        let job = job.clone();
+        run_command(
+            &["rm", "-f", &job.pid_file.to_string_lossy()],
+            BTreeMap::new(),
+        );
+        Some(job)
+    }
+
+    /// Kill the job `id`, or the last one still running
+    pub(crate) fn kill(&mut self, id: Option<usize>) -> Result<usize, String> {
+        let job = match id {
+            Some(id) => self
+                .list
+                .iter()
+                .find(|job| job.id == id)
+                .ok_or_else(|| format!("There is no job {id}"))?,
+            None => self
+                .list
+                .iter()
+                .rev()
+                .find(|job| job.status == Status::Running)
+                .ok_or_else(|| String::from("No job is running"))?,
+        };
+        if job.status != Status::Running {
+            return Err(format!("The job {} is not running", job.id));
+        }
+
+        run_command(
+            &[
+                "sh",
+                "-c",
+                r#"kill "$(cat "$0")""#,
+                &job.pid_file.to_string_lossy(),
+            ],
+            BTreeMap::new(),
+        );
+        Ok(job.id)
+    }
+
+    /// Forget the jobs that are done, return how many there were
+    pub(crate) fn clear(&mut self) -> usize {
+        let count = self.list.len();
+        self.list.retain(|job| job.status == Status::Running);
+        self.select(self.list.len().saturating_sub(1));
+
+        count - self.list.len()
+    }
+
+    pub(crate) fn running(&self) -> usize {
+        self.list
+            .iter()
+            .filter(|job| job.status == Status::Running)
+            .count()
+    }
+
+    pub(crate) fn selected(&self) -> Option<&Job> {
+        self.list.get(self.selected)
+    }
+
+    pub(crate) fn select_up(&mut self) {
+        if !self.list.is_empty() {
+            self.select((self.selected + self.list.len() - 1) % self.list.len());
+        }
+    }
+
+    pub(crate) fn select_down(&mut self) {
+        if !self.list.is_empty() {
+            self.select((self.selected + 1) % self.list.len());
+        }
+    }
+
+    /// Move the output shown by `lines`, up when negative
+    pub(crate) fn scroll(&mut self, lines: isize) {
+        let count = self.selected().map(|job| job.output().len()).unwrap_or(0);
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(lines)
+            .min(count.saturating_sub(1));
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        self.scroll = 0;
+    }
+
+    /// A new file of the host to write the output of the next job in
+    pub(crate) fn output_file(&self) -> Result<PathBuf, String> {
+        Ok(self.folder()?.join(format!("{}.txt", self.last_id + 1)))
+    }
+
+    fn folder(&self) -> Result<&PathBuf, String> {
+        self.folder
+            .as_ref()
+            .ok_or_else(|| String::from("the folder of the jobs is not created yet"))
+    }
+}
+
+/// Ask the host for a new folder only this plugin writes in, the result comes in a `RunCommandResult` event.
+/// Its name cannot be guessed, so no other session or user can put a file or a link in its place.
+pub(crate) fn create_folder() {
+    let context = BTreeMap::from([("source".to_string(), FOLDER_SOURCE.to_string())]);
+    run_command(
+        &[
+            "sh",
+            "-c",
+            r#"mktemp -d "${TMPDIR:-/tmp}/zellij-console-XXXXXX""#,
+        ],
+        context,
+    );
+}
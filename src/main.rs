@@ -1,13 +1,29 @@
 mod action;
+mod config;
+mod executables;
+mod focus;
+mod history;
+mod jobs;
+mod shell;
 mod ui;
 
-use action::{Action, ActionList, Interface};
+use action::sequence::{Condition, Sequence};
+use action::token::quote;
+use action::{Action, ActionList, Interface, Pane, MAX_FILES};
+use config::Config;
+use focus::{FocusHistory, PaneRef};
+use history::History;
+use jobs::{Job, Jobs, Status};
 
 use strum::EnumMessage;
 use zellij_tile::prelude::*;
 
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Seconds without typing before the slow checks of the prompt are done
+const VALIDATION_DELAY: f64 = 0.3;
 
 #[derive(Default)]
 struct DisplaySize {
@@ -17,10 +33,113 @@ struct DisplaySize {
 
 #[derive(Default)]
 struct ZellijState {
-    // current_session: SessionInfo,
-    // mode_info: ModeInfo,
-    // current_tab: TabInfo,
-    // last_pane: PaneManifest,
+    /// Where the session started
+    initial_cwd: PathBuf,
+    /// Our own plugin pane
+    plugin_id: u32,
+    session_name: Option<String>,
+    tabs: Vec<TabInfo>,
+    panes: PaneManifest,
+    /// The last file opened with `Edit`
+    last_file: Option<PathBuf>,
+}
+
+impl ZellijState {
+    /// The `$VARIABLES` that can be used in the commands, `focused` is the last pane focused before this plugin
+    fn variables(&self, focused: Option<PaneRef>) -> BTreeMap<String, String> {
+        let mut variables = BTreeMap::new();
+        let focused_tab = get_focused_tab(&self.tabs);
+
+        // Zellij does not tell the panes’ cwd to the plugins, only the one the session started in is known
+        variables.insert(
+            "INITIAL_CWD".to_string(),
+            self.initial_cwd.display().to_string(),
+        );
+        if let Some(pane) = focused {
+            variables.insert("FOCUSED_PANE_ID".to_string(), pane.id.to_string());
+        }
+        if let Some(tab) = &focused_tab {
+            variables.insert("TAB_NAME".to_string(), tab.name.clone());
+        }
+        if let Some(session_name) = &self.session_name {
+            variables.insert("SESSION_NAME".to_string(), session_name.clone());
+        }
+        if let Some(last_file) = &self.last_file {
+            variables.insert("LAST_FILE".to_string(), last_file.display().to_string());
+        }
+
+        variables
+    }
+
+    /// Position of the focused tab, from 0
+    fn focused_tab(&self) -> Option<usize> {
+        get_focused_tab(&self.tabs).map(|tab| tab.position)
+    }
+
+    fn pane_info(&self, pane: PaneRef) -> Option<&PaneInfo> {
+        self.panes
+            .panes
+            .values()
+            .flatten()
+            .find(|info| info.id == pane.id && info.is_plugin == pane.plugin)
+    }
+
+    /// Whether the panes have frames: the content of a tiled pane is then smaller than it
+    fn frames_shown(&self) -> Option<bool> {
+        let tiled: Vec<_> = self
+            .panes
+            .panes
+            .values()
+            .flatten()
+            .filter(|info| info.is_selectable && !info.is_floating && !info.is_suppressed)
+            .collect();
+
+        (!tiled.is_empty()).then(|| {
+            tiled
+                .iter()
+                .any(|info| info.pane_content_rows < info.pane_rows)
+        })
+    }
+
+    /// The panes of every tab, in the order of the tabs
+    fn panes(&self) -> Vec<Pane> {
+        let mut tabs: Vec<_> = self.panes.panes.iter().collect();
+        tabs.sort_by_key(|(position, _)| **position);
+
+        tabs.into_iter()
+            .flat_map(|(position, panes)| panes.iter().map(move |pane| (*position, pane)))
+            .map(|(position, pane)| {
+                let command = if pane.is_plugin {
+                    // `zellij:strider` or `file:/path/to/plugin.wasm`
+                    pane.plugin_url.as_ref().map(|url| {
+                        let name = url.rsplit([':', '/']).next().unwrap_or(url);
+                        name.trim_end_matches(".wasm").to_string()
+                    })
+                } else {
+                    pane.terminal_command
+                        .as_ref()
+                        .and_then(|command| command.split_whitespace().next())
+                        .map(|program| program.rsplit('/').next().unwrap_or(program).to_string())
+                };
+
+                Pane {
+                    id: pane.id,
+                    plugin: pane.is_plugin,
+                    title: pane.title.clone(),
+                    command,
+                    tab: self
+                        .tabs
+                        .iter()
+                        .find(|tab| tab.position == position)
+                        .map(|tab| tab.name.clone())
+                        .unwrap_or_default(),
+                    exited: pane.exited,
+                    exit_status: pane.exit_status,
+                    selectable: pane.is_selectable,
+                }
+            })
+            .collect()
+    }
 }
 
 #[derive(Default)]
@@ -30,12 +149,32 @@ struct State {
     search_filter: EnvironmentFrom,
     display: DisplaySize,
     zellij_state: ZellijState,
+    config: Config,
+    /// Why the last command failed, cleared on the next key press
+    last_error: Option<String>,
+    /// Macros being executed, to detect the ones calling themselves
+    running_macros: Vec<String>,
+    /// Command opening many files, waiting for a second `Enter`
+    confirming: Option<String>,
+    /// Step waiting for the user (Ex: the confirmation of `Edit`) and the command it is in,
+    /// the next `Enter` resumes from it if the command did not change
+    resume_step: Option<(String, usize)>,
+    /// Timers started while typing, the prompt is validated when the last one ends
+    validation_timers: usize,
+    /// The commands run from the plugin pane
+    history: History,
+    /// The programs found in the `$PATH` of each environment, by `EnvironmentFrom::as_str`
+    executables: BTreeMap<String, Vec<String>>,
+    /// The commands run in the background with `Job`
+    jobs: Jobs,
+    /// The panes the actions apply to when none is given
+    focus: FocusHistory,
 }
 
 register_plugin!(State);
 
 impl ZellijPlugin for State {
-    fn load(&mut self, _configuration: BTreeMap<String, String>) {
+    fn load(&mut self, configuration: BTreeMap<String, String>) {
         request_permission(&[
             PermissionType::ChangeApplicationState,
             PermissionType::MessageAndLaunchOtherPlugins,
@@ -47,12 +186,49 @@ impl ZellijPlugin for State {
         ]);
         subscribe(&[
             EventType::Key,
-            // EventType::ModeUpdate,
-            // EventType::PaneUpdate,
-            // EventType::SessionUpdate,
-            // EventType::TabUpdate,
+            EventType::ModeUpdate,
+            EventType::PaneUpdate,
+            EventType::SessionUpdate,
+            EventType::PermissionRequestResult,
+            EventType::RunCommandResult,
+            EventType::TabUpdate,
+            EventType::Timer,
         ]);
 
+        self.config = Config::new(&configuration);
+        for error in &self.config.errors {
+            eprintln!("Configuration error: {error}");
+        }
+        let aliases = self.config.aliases.clone();
+        let macros = self.config.macros.clone();
+        self.action.update_context(|context| {
+            context.aliases = aliases;
+            context.macros = macros;
+        });
+
+        self.history = History::load();
+
+        // Scanning again takes a while, start with the previous result
+        for environment in [
+            EnvironmentFrom::ZellijSession,
+            EnvironmentFrom::DefaultShell,
+        ] {
+            if let Some(executables) = executables::read_cache(&environment) {
+                self.executables
+                    .insert(environment.as_str().to_string(), executables);
+            }
+        }
+        self.update_executables();
+
+        let plugin_ids = get_plugin_ids();
+        self.zellij_state.plugin_id = plugin_ids.plugin_id;
+        self.focus.plugin_id = plugin_ids.plugin_id;
+        self.zellij_state.initial_cwd = plugin_ids.initial_cwd;
+        let initial_cwd = self.zellij_state.initial_cwd.clone();
+        self.action
+            .update_context(|context| context.initial_cwd = initial_cwd);
+        self.update_variables();
+
         // TODO: This may change as I’m not convinced the `configuration`’s API is good for this
         // self.action
         //     .set(configuration.get("command").unwrap_or(&Default::default()));
@@ -66,36 +242,93 @@ impl ZellijPlugin for State {
                 self.handle_key(key);
                 should_render = true;
             }
-            // Event::ModeUpdate(mode_info) => {
-            //     self.mode_info = mode_info;
-            //     should_render = true;
-            // }
-            // Event::PaneUpdate(pane_info) => {
-            //     self.last_pane = pane_info;
-            //     // should_render = true;
-            // }
-            Event::PermissionRequestResult(_status) => {
+            Event::ModeUpdate(mode_info) => {
+                if mode_info.session_name.is_some() {
+                    self.zellij_state.session_name = mode_info.session_name;
+                    should_render = self.update_variables();
+                }
+            }
+            Event::PaneUpdate(pane_manifest) => {
+                self.zellij_state.panes = pane_manifest;
+                self.update_focus();
+                should_render = self.update_variables() | self.update_panes();
+            }
+            Event::PermissionRequestResult(status) => {
                 // should_render = true;
+                if status == PermissionStatus::Granted {
+                    executables::scan(&EnvironmentFrom::ZellijSession, &self.config.shell);
+                    // The shell must be known to read its `$PATH`
+                    if self.config.shell.path.is_some() {
+                        executables::scan(&EnvironmentFrom::DefaultShell, &self.config.shell);
+                    } else {
+                        shell::detect();
+                    }
+                    jobs::create_folder();
+                }
+            }
+            Event::RunCommandResult(exit_code, stdout, stderr, context) => {
+                if context.get("source").map(String::as_str) == Some(jobs::SOURCE) {
+                    if let Some(job) = self.jobs.finish(&context, exit_code, &stdout, &stderr) {
+                        self.job_finished(job);
+                        should_render = true;
+                    }
+                } else if context.get("source").map(String::as_str) == Some(jobs::FOLDER_SOURCE) {
+                    let path = String::from_utf8_lossy(&stdout).trim().to_string();
+                    if path.is_empty() {
+                        eprintln!(
+                            "The folder of the jobs could not be created: {}",
+                            String::from_utf8_lossy(&stderr)
+                        );
+                    } else {
+                        self.jobs.folder = Some(PathBuf::from(path));
+                    }
+                } else if context.get("source").map(String::as_str) == Some(shell::SOURCE) {
+                    let path = String::from_utf8_lossy(&stdout).trim().to_string();
+                    if !path.is_empty() {
+                        self.config.shell.path = Some(PathBuf::from(path));
+                    }
+                    executables::scan(&EnvironmentFrom::DefaultShell, &self.config.shell);
+                } else if context.get("source").map(String::as_str) == Some(executables::SOURCE) {
+                    let environment = context.get("environment").cloned().unwrap_or_default();
+                    let found = executables::parse(&stdout);
+                    if found.is_empty() {
+                        eprintln!(
+                            "No executable found for {environment}: {}",
+                            String::from_utf8_lossy(&stderr)
+                        );
+                    } else {
+                        let from = [
+                            EnvironmentFrom::ZellijSession,
+                            EnvironmentFrom::DefaultShell,
+                        ]
+                        .into_iter()
+                        .find(|e| e.as_str() == environment);
+                        if let Some(from) = from {
+                            executables::write_cache(&from, &found);
+                        }
+                        self.executables.insert(environment, found);
+                        self.update_executables();
+                    }
+                }
+            }
+            Event::SessionUpdate(sessions_info, _resurrectable_sessions) => {
+                if let Some(session) = sessions_info.into_iter().find(|s| s.is_current_session) {
+                    self.zellij_state.session_name = Some(session.name);
+                    should_render = self.update_variables();
+                }
+            }
+            Event::TabUpdate(tab_info) => {
+                self.zellij_state.tabs = tab_info;
+                self.update_focus();
+                // The panes know the name of their tab
+                should_render = self.update_variables() | self.update_tabs() | self.update_panes();
+            }
+            Event::Timer(_) => {
+                self.validation_timers = self.validation_timers.saturating_sub(1);
+                if self.validation_timers == 0 {
+                    should_render = self.action.validate();
+                }
             }
-            // Event::SessionUpdate(sessions_info, _resurrectable_sessions) => {
-            //     sessions_info.iter().for_each(|s| {
-            //         eprintln!(
-            //             "Session infos: name: {}, current: {}, clients: {}",
-            //             s.name, s.is_current_session, s.connected_clients
-            //         )
-            //     });
-            //     self.zellij_state.current_session = sessions_info
-            //         .iter()
-            //         .find(|s| s.is_current_session)
-            //         .unwrap()
-            //         .clone();
-            // }
-            // Event::TabUpdate(tab_info) => {
-            //     self.current_tab = tab_info.iter().find(|t| t.active).unwrap().clone();
-            //     // eprintln!("Event::TabUpdate: {tab_info:#?}\n");
-            //     // eprintln!("Current Tab: {:#?}\n", self.current_tab);
-            //     // should_render = true;
-            // }
             _ => unimplemented!("{:?}", event),
         };
 
@@ -103,7 +336,7 @@ impl ZellijPlugin for State {
     }
 
     fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
-        let mut should_render = false; // TODO: needed sometimes? apparently not since changing the action already update the interface… but check it… (if needed add when `set`ting the action?)
+        let should_render = false; // TODO: needed sometimes? apparently not since changing the action already update the interface… but check it… (if needed add when `set`ting the action?)
         let interface = Interface::Pipe;
 
         eprintln!("received message from pipe {:#?}", pipe_message);
@@ -125,33 +358,34 @@ impl ZellijPlugin for State {
         //     * Not sure if the commands run or wait in those scenarios.
         //     * The `-c, --plugin-configuration <PLUGIN_CONFIGURATION>` can be used (currently with any config, since it is not used) to always create a new plugin and found yourself in the first scenario.
 
+        // A step waiting in the plugin pane belongs to another command
+        self.resume_step = None;
         if let Some(command) = &pipe_message.payload {
             self.action.set(command, &interface)
         } else if let Some(command) = pipe_message.args.get("command") {
             self.action.set(command, &interface)
         };
 
-        let force = pipe_message.args.get("force_available").is_some(); // TODO: just "force"?
-        let res = format!("{}", self.action.action());
-        let res = if force {
-            // Cannot merge those 2 if with `&&` because of: eRFC 2497, "if- and while-let-chains, take 2" see tracking issue https://github.com/rust-lang/rust/issues/53667
-            // This is annoying for the elses
-            if let ActionList::Unavailable {
-                action,
-                calling_interface,
-            } = self.action.action()
-            {
-                let res = format!("{}", action);
-                self.start_action(Some((**action).clone()));
-                res
+        let force = pipe_message.args.contains_key("force_available"); // TODO: just "force"?
+        if force {
+            self.action.force();
+        }
+        let mut res = format!("{}", self.action.sequence());
+        let parse_errors: Vec<_> = self
+            .action
+            .sequence()
+            .errors()
+            .map(|error| ui::render_parse_error(self.action.as_str(), error))
+            .collect();
+        self.start_action(interface);
+        if let Some(error) = self.last_error.take() {
+            // The position of the error is more helpful than the name of the failed step
+            if parse_errors.is_empty() {
+                res = format!("{res}\nError: {error}");
             } else {
-                self.start_action(None);
-                res
+                res = format!("{res}\nError:\n{}", parse_errors.join("\n"));
             }
-        } else {
-            self.start_action(None);
-            res
-        };
+        }
 
         match pipe_message.source {
             PipeSource::Cli(pipe_name) => {
@@ -195,15 +429,58 @@ impl ZellijPlugin for State {
 
 impl State {
     pub fn handle_key(&mut self, key: Key) {
+        self.last_error = None;
+        if key != Key::Char('\n') {
+            self.confirming = None;
+            self.resume_step = None;
+        }
+
+        let keys = &self.config.keys;
+        if keys.accept_autosuggestion.contains(&key)
+            && self
+                .action
+                .accept_autosuggestion(&self.history, &Interface::Pane)
+        {
+            return;
+        }
+        if keys.toggle_floating.contains(&key) {
+            self.should_open_floating = !self.should_open_floating;
+            return;
+        }
+        if keys.toggle_environment.contains(&key) {
+            self.search_filter.progress(); // TODO: should also be a toggelable bool
+            self.update_executables();
+            return;
+        }
+
+        // The list of jobs is browsed while it is shown
+        if let ActionList::Jobs = self.action.sequence().last() {
+            match key {
+                Key::Down => return self.jobs.select_down(),
+                Key::Up => return self.jobs.select_up(),
+                Key::PageDown => return self.jobs.scroll(ui::JOB_OUTPUT_ROWS as isize),
+                Key::PageUp => return self.jobs.scroll(-(ui::JOB_OUTPUT_ROWS as isize)),
+                _ => {}
+            }
+        }
+
         match key {
             Key::Down => self.action.selection_down(),
             Key::Up => self.action.selection_up(),
-            Key::Char('\n') => self.start_action(None),
-            // Key::BackTab => self.open_search_result_in_terminal(),
-            Key::Ctrl('f') => {
-                self.should_open_floating = !self.should_open_floating;
+            Key::Char('\n') => {
+                // `Enter` picks the selected candidate when they are listed
+                if !(self.action.completion().is_some() && self.action.complete(&Interface::Pane)) {
+                    self.start_action(Interface::Pane)
+                }
             }
-            Key::Ctrl('e') => self.search_filter.progress(), // TODO: should also be a toggelable bool
+            Key::Char('\t') => {
+                if !self.action.complete(&Interface::Pane)
+                    && self.action.accept_suggestion(&Interface::Pane)
+                {
+                    self.start_action(Interface::Pane);
+                }
+            }
+            // Key::BackTab => self.open_search_result_in_terminal(),
             // Key::Esc | Key::Ctrl('c') => {
             //     if !self.search_term.is_empty() {
             //         self.clear_state();
@@ -215,6 +492,64 @@ impl State {
         }
     }
 
+    /// Give the programs of the current environment to the parser
+    fn update_executables(&mut self) {
+        let executables = self
+            .executables
+            .get(self.search_filter.as_str())
+            .cloned()
+            .unwrap_or_default();
+        self.action
+            .update_context(|context| context.executables = executables);
+    }
+
+    /// Give the new values of the variables to the parser, return whether they changed
+    fn update_variables(&mut self) -> bool {
+        let variables = self.zellij_state.variables(self.focus.previous());
+        let changed = variables != self.action.context().variables;
+        if changed {
+            self.action
+                .update_context(|context| context.variables = variables);
+        }
+
+        changed
+    }
+
+    /// Give the new tabs to the parser, return whether they changed
+    fn update_tabs(&mut self) -> bool {
+        let mut tabs = self.zellij_state.tabs.clone();
+        tabs.sort_by_key(|tab| tab.position);
+        let names: Vec<_> = tabs.into_iter().map(|tab| tab.name).collect();
+        let focused = self.zellij_state.focused_tab();
+
+        let context = self.action.context();
+        let changed = names != context.tabs || focused != context.focused_tab;
+        if changed {
+            self.action.update_context(|context| {
+                context.tabs = names;
+                context.focused_tab = focused;
+            });
+        }
+
+        changed
+    }
+
+    /// Give the new panes to the parser, return whether they changed
+    fn update_panes(&mut self) -> bool {
+        let panes = self.zellij_state.panes();
+        let changed = panes != self.action.context().panes;
+        if changed {
+            self.action.update_context(|context| context.panes = panes);
+        }
+
+        changed
+    }
+
+    fn update_focus(&mut self) {
+        let tab = self.zellij_state.focused_tab();
+        self.focus.update(tab, &self.zellij_state.panes);
+    }
+
     pub fn change_size(&mut self, rows: usize, cols: usize) {
         self.display.rows = rows;
         self.display.columns = cols;
@@ -226,9 +561,11 @@ impl State {
         match key {
             Key::Char(character) => {
                 self.action.push(character, &interface);
+                self.schedule_validation();
             }
             Key::Backspace => {
                 self.action.pop(&interface);
+                self.schedule_validation();
                 if self.action.len() == 0 {
                     // self.clear_state();
                 }
@@ -237,26 +574,297 @@ impl State {
         }
     }
 
-    fn start_action(&mut self, override_action: Option<ActionList>) {
-        let interface = Interface::Pane; // TODO: receive from parameter?
-        let action = override_action.unwrap_or_else(|| self.action.action().clone());
-        let mut done = true;
+    /// Validate the prompt once the user stops typing for a moment
+    fn schedule_validation(&mut self) {
+        self.validation_timers += 1;
+        set_timeout(VALIDATION_DELAY);
+    }
+
+    /// Open the output of `Run … | Edit`, and tell why the command failed
+    fn job_finished(&mut self, job: Job) {
+        let Some(file) = job.edit else {
+            return;
+        };
+
+        match job.status {
+            Status::Exited(0) => {}
+            Status::Exited(code) => {
+                self.last_error = Some(format!(
+                    "`{}` failed with the code {code}: {}",
+                    job.command,
+                    job.stderr.trim()
+                ))
+            }
+            Status::Running | Status::Killed => return,
+        }
+
+        if self.should_open_floating {
+            open_file_floating(file, None); // TODO: Make it possible to provide the coordinates
+        } else {
+            open_file(file);
+        }
+    }
+
+    /// Run the `command` in the background, see `Jobs`
+    fn start_job(
+        &mut self,
+        command: CommandToRun,
+        env: BTreeMap<String, String>,
+        edit: Option<FileToOpen>,
+    ) -> Outcome {
+        let CommandToRun { path, args, cwd } = command;
+        let shown = env
+            .iter()
+            .map(|(key, value)| format!("{key}={}", quote(value)))
+            .chain(std::iter::once(quote(&path.to_string_lossy())))
+            .chain(args.iter().map(|arg| quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let (path, args) = self.in_environment(path, args);
+        let program: Vec<_> = std::iter::once(path.to_string_lossy().to_string())
+            .chain(args)
+            .collect();
+        let cwd = self.zellij_state.initial_cwd.join(cwd.unwrap_or_default());
+
+        match self.jobs.start(shown, &program, env, cwd, edit) {
+            Ok(_) => Outcome::Done,
+            Err(error) => Outcome::Failed(error),
+        }
+    }
+
+    /// `Run … | Edit`: the command runs in the background, its output is written in the file of `edit` then opened
+    fn edit_output(&mut self, run: ActionList, edit: ActionList) -> Outcome {
+        let (ActionList::Run { command, env, .. }, ActionList::Edit { files, .. }) = (run, edit)
+        else {
+            return Outcome::Failed(String::from(
+                "only the output of `Run` can be given to `Edit`",
+            ));
+        };
+
+        let file = match files.into_iter().next() {
+            Some(file) => FileToOpen {
+                path: self
+                    .zellij_state
+                    .initial_cwd
+                    .join(file.cwd.unwrap_or_default())
+                    .join(file.path),
+                cwd: None,
+                ..file
+            },
+            None => match self.jobs.output_file() {
+                Ok(path) => FileToOpen {
+                    path,
+                    ..Default::default()
+                },
+                Err(error) => return Outcome::Failed(error),
+            },
+        };
+
+        self.start_job(command, env, Some(file))
+    }
+
+    /// The program and arguments running `path` with `args` in the environment chosen by the user
+    fn in_environment(&self, path: PathBuf, args: Vec<String>) -> (PathBuf, Vec<String>) {
+        match self.search_filter {
+            EnvironmentFrom::ZellijSession => (path, args),
+            EnvironmentFrom::DefaultShell => {
+                let words: Vec<_> = std::iter::once(path.to_string_lossy().to_string())
+                    .chain(args)
+                    .collect();
+                self.config.shell.command(&words)
+            }
+        }
+    }
+
+    /// A new tab with the `files` stacked in it
+    fn edit_layout(&self, files: &[FileToOpen]) -> String {
+        let panes: String = files
+            .iter()
+            .map(|file| {
+                let folder = file.cwd.as_ref().unwrap_or(&self.zellij_state.initial_cwd);
+                let path = folder.join(&file.path);
+                match file.line_number {
+                    // The layouts cannot give a line to the editor of Zellij, run the user’s editor instead
+                    Some(line) => format!(
+                        "            pane command=\"sh\" close_on_exit=true {{\n                args \"-c\" {:?} {:?} \"{line}\"\n            }}\n",
+                        r#"exec "${VISUAL:-${EDITOR:-vi}}" "+$1" "$0""#,
+                        path
+                    ),
+                    None => format!("            pane edit={path:?}\n"),
+                }
+            })
+            .collect();
+
+        format!(
+            "layout {{\n    tab {{\n        pane stacked=true {{\n{panes}        }}\n    }}\n}}\n"
+        )
+    }
+
+    /// Focus the pane an action applies to, the last focused one by default, since Zellij acts on the focused pane
+    fn focus_target(&self, pane: Option<PaneRef>) -> Result<PaneRef, String> {
+        let pane = pane
+            .or(self.focus.previous())
+            .ok_or_else(|| String::from("no pane was focused yet"))?;
+        if pane.plugin {
+            focus_plugin_pane(pane.id, false);
+        } else {
+            focus_terminal_pane(pane.id, false);
+        }
+
+        Ok(pane)
+    }
+
+    /// Whether the `pane` floats, the last focused one by default
+    fn pane_floats(&self, pane: Option<PaneRef>) -> Option<bool> {
+        let pane = pane.or(self.focus.previous())?;
+        self.zellij_state
+            .pane_info(pane)
+            .map(|info| info.is_floating)
+    }
+
+    /// Give the focus back to the console once an action applied to another pane
+    fn focus_console(&self, interface: &Interface) {
+        if let Interface::Pane = interface {
+            focus_plugin_pane(self.zellij_state.plugin_id, false);
+        }
+    }
+
+    /// A new tab whose terminal starts in `cwd`
+    fn tab_layout(&self, name: Option<&str>, cwd: &Path) -> String {
+        let name = name
+            .map(|name| format!(" name={name:?}"))
+            .unwrap_or_default();
+        let cwd = self.zellij_state.initial_cwd.join(cwd);
+
+        format!("layout {{\n    tab{name} cwd={cwd:?} {{\n        pane\n    }}\n}}\n")
+    }
+
+    fn start_action(&mut self, interface: Interface) {
+        self.action.validate();
+        let sequence = self.action.sequence().clone();
+        let command = self.action.as_str().to_string();
+        let first = match self.resume_step.take() {
+            Some((resumed, index)) if resumed == command => index,
+            _ => 0,
+        };
+        let (outcome, pending_step) = self.run_steps(&sequence, &interface, first);
+        // A step can rewrite the prompt (Ex: `Help` writes the selected action), it is then run from its start
+        self.resume_step = pending_step
+            .filter(|_| self.action.as_str() == command)
+            .map(|index| (command, index));
+        match outcome {
+            Outcome::Done => {
+                if let Interface::Pane = interface {
+                    self.history.push(self.action.as_str());
+                }
+                self.action.clear();
+            }
+            Outcome::Pending => {}
+            Outcome::Failed(error) => {
+                eprintln!("{error}");
+                self.last_error = Some(error);
+            }
+        }
+    }
+
+    /// Execute the steps in order, following the conditions given by `;`, `&&` and `||`
+    fn run_sequence(&mut self, sequence: &Sequence, interface: &Interface) -> Outcome {
+        self.run_steps(sequence, interface, 0).0
+    }
+
+    /// Execute the steps from the `first` one, stop at the one waiting for the user and return its index
+    fn run_steps(
+        &mut self,
+        sequence: &Sequence,
+        interface: &Interface,
+        first: usize,
+    ) -> (Outcome, Option<usize>) {
+        let mut succeeded = true;
+        let mut failure = None;
+        // The steps that succeeded
+        let mut done = Vec::new();
+
+        let steps = sequence.steps();
+        for (index, step) in steps.iter().enumerate().skip(first) {
+            // It was started with the previous step
+            if step.condition == Condition::Pipe || !step.condition.should_run(succeeded) {
+                continue;
+            }
+            // A command can end with a `;`, like in a shell
+            let last = index + 1 == steps.len();
+            if last && index > 0 && step.condition == Condition::Always && step.tokens().is_empty()
+            {
+                continue;
+            }
+
+            let outcome = match steps.get(index + 1) {
+                Some(next) if next.condition == Condition::Pipe => {
+                    self.edit_output(step.action.clone(), next.action.clone())
+                }
+                _ => self.execute(step.action.clone(), &step.command, interface),
+            };
+            // Like in a shell, the sequence ends with the status of the last step executed:
+            // the steps that already ran must not run again because an earlier one failed
+            match outcome {
+                // The next steps must not run before the user answers this one
+                Outcome::Pending => return (Outcome::Pending, Some(index)),
+                Outcome::Done => {
+                    succeeded = true;
+                    done.push(format!("`{}`", step.command));
+                    if let Some(error) = failure.take() {
+                        eprintln!("{error}");
+                    }
+                }
+                Outcome::Failed(error) => {
+                    succeeded = false;
+                    failure = Some(format!("`{}` failed: {error}", step.command));
+                }
+            }
+        }
+
+        match failure {
+            // Pressing `Enter` again would run them a second time
+            Some(error) if !done.is_empty() => (
+                Outcome::Failed(format!("{error} (already done: {})", done.join(", "))),
+                None,
+            ),
+            Some(error) => (Outcome::Failed(error), None),
+            None => (Outcome::Done, None),
+        }
+    }
+
+    fn execute(&mut self, action: ActionList, command: &str, interface: &Interface) -> Outcome {
+        let mut outcome = Outcome::Done;
         match action {
-            // ActionList::ClearScreen => {
-            //     // TODO: Clear applies on the focused pane, focus to the previous one before clearing the screen/scrollback
-            //     // focus_previous_pane();
-            //     clear_screen();
-            // }
+            ActionList::ClearScreen { pane } => {
+                let Some(id) = pane.or(self.focus.previous_terminal()) else {
+                    return Outcome::Failed(String::from("no terminal pane was focused yet"));
+                };
+                // Zellij clears the focused pane, come back to the console once done
+                focus_terminal_pane(id, false);
+                clear_screen();
+                self.focus_console(interface);
+            }
             // ActionList::CloseFocus => close_focus(),
-            // ActionList::CloseFocusTab => close_focused_tab(),
-            // ActionList::ClosePluginPane { id } => match id {
-            //     Some(id) => close_plugin_pane(id),
-            //     None => done = false,
-            // },
-            // ActionList::CloseTerminalPane { id } => match id {
-            //     Some(id) => close_terminal_pane(id),
-            //     None => done = false,
-            // },
+            ActionList::CloseTab { position } => {
+                let focused = self.zellij_state.focused_tab();
+                match (position, focused) {
+                    (Some(position), Some(focused)) if position != focused => {
+                        // Zellij only closes the focused tab, and counts the tabs from 1
+                        switch_tab_to(position as u32 + 1);
+                        close_focused_tab();
+                        let back = if focused > position {
+                            focused - 1
+                        } else {
+                            focused
+                        };
+                        switch_tab_to(back as u32 + 1);
+                    }
+                    _ => close_focused_tab(),
+                }
+            }
+            ActionList::ClosePluginPane { id } => close_plugin_pane(id),
+            ActionList::CloseTerminalPane { id } => close_terminal_pane(id),
             ActionList::DetachEveryone => {
                 eprintln!("send message to pipe? DE");
                 if let Interface::Pane = interface {
@@ -264,7 +872,7 @@ impl State {
                     pipe_message_to_plugin(
                         MessageToPlugin::new("message_name")
                             .with_plugin_url("zellij::OWN_URL")
-                            .with_payload(self.action.command()),
+                            .with_payload(command),
                     );
                 }
                 detach();
@@ -279,30 +887,141 @@ impl State {
                     pipe_message_to_plugin(
                         MessageToPlugin::new("message_name")
                             .with_plugin_url("zellij::OWN_URL")
-                            .with_payload(self.action.command()),
+                            .with_payload(command),
                     );
                 }
             }
-            // ActionList::EditScrollback => {
-            //     // TODO: Edit scrollback applies on the focused pane, focus to the previous one before clearing the screen/scrollback
-            //     // focus_previous_pane();
-            //     edit_scrollback();
-            // }
-            ActionList::Edit(FileToOpen {
-                path,
-                line_number,
-                cwd,
-            }) => {
-                let file = FileToOpen {
-                    path: path.to_owned(),
-                    line_number: line_number.to_owned(),
-                    cwd: cwd.to_owned(),
+            ActionList::EditScrollback { pane } => {
+                let Some(id) = pane.or(self.focus.previous_terminal()) else {
+                    return Outcome::Failed(String::from("no terminal pane was focused yet"));
                 };
+                // Zellij edits the scrollback of the focused pane
+                focus_terminal_pane(id, false);
+                edit_scrollback();
+            }
+            ActionList::Edit {
+                files,
+                new_tab,
+                confirmed,
+                ..
+            } => {
+                if files.len() > MAX_FILES && !confirmed {
+                    if let Interface::Pipe = interface {
+                        return Outcome::Failed(format!(
+                            "{} files match, add `---yes` to open more than {MAX_FILES} files",
+                            files.len()
+                        ));
+                    }
+                    // The second `Enter` confirms
+                    if self.confirming.as_deref() != Some(command) {
+                        self.confirming = Some(command.to_string());
+                        return Outcome::Pending;
+                    }
+                }
+                self.confirming = None;
 
-                if self.should_open_floating {
-                    open_file_floating(file, None); // TODO: Make it possible to provide the coordinates
+                if let Some(file) = files.last() {
+                    self.zellij_state.last_file = Some(match &file.cwd {
+                        Some(cwd) => cwd.join(&file.path),
+                        None => file.path.clone(),
+                    });
+                    self.update_variables();
+                }
+
+                if new_tab {
+                    new_tabs_with_layout(&self.edit_layout(&files));
                 } else {
-                    open_file(file);
+                    // One pane per file, Zellij cannot add them to a stack of the current tab
+                    for file in files {
+                        if self.should_open_floating {
+                            open_file_floating(file, None); // TODO: Make it possible to provide the coordinates
+                        } else {
+                            open_file(file);
+                        }
+                    }
+                }
+            }
+            ActionList::Embed { pane } | ActionList::Float { pane }
+                if self.pane_floats(pane) == Some(matches!(action, ActionList::Float { .. })) => {}
+            ActionList::Embed { pane } => {
+                if let Err(error) = self.focus_target(pane) {
+                    return Outcome::Failed(error);
+                }
+                toggle_pane_embed_or_eject();
+                self.focus_console(interface);
+            }
+            // The console would hide the floating panes if it took the focus back
+            ActionList::Float { pane } => {
+                if let Err(error) = self.focus_target(pane) {
+                    return Outcome::Failed(error);
+                }
+                toggle_pane_embed_or_eject();
+            }
+            ActionList::Frames { shown } => {
+                if shown.is_none() || shown != self.zellij_state.frames_shown() {
+                    toggle_pane_frames();
+                }
+            }
+            ActionList::Fullscreen { pane } => {
+                if let Err(error) = self.focus_target(pane) {
+                    return Outcome::Failed(error);
+                }
+                toggle_focus_fullscreen();
+            }
+            ActionList::Focus {
+                panes, selected, ..
+            } => match panes.get(selected) {
+                // Zellij switches to the tab of the pane
+                Some(pane) if pane.plugin => focus_plugin_pane(pane.id, false),
+                Some(pane) => focus_terminal_pane(pane.id, false),
+                None => return Outcome::Failed(String::from("no pane matches")),
+            },
+            ActionList::GoToTab { position } => switch_tab_to(position as u32 + 1),
+            ActionList::Move { direction, pane } => {
+                let floats = match self.focus_target(pane) {
+                    Ok(target) => self.pane_floats(Some(target)),
+                    Err(error) => return Outcome::Failed(error),
+                };
+                match direction {
+                    Some(direction) => move_pane_with_direction(direction),
+                    None => move_pane(),
+                }
+                if floats != Some(true) {
+                    self.focus_console(interface);
+                }
+            }
+            ActionList::MoveTab { offset } => {
+                if offset != 0 {
+                    // Zellij has no plugin command to move a tab, its cli has one
+                    let Some(session) = self.zellij_state.session_name.clone() else {
+                        return Outcome::Failed(String::from("the session name is not known yet"));
+                    };
+                    let direction = if offset < 0 { "left" } else { "right" };
+                    run_command(
+                        &[
+                            "sh",
+                            "-c",
+                            r#"for _ in $(seq "$1"); do zellij --session "$0" action move-tab "$2" || exit; done"#,
+                            &session,
+                            &offset.unsigned_abs().to_string(),
+                            direction,
+                        ],
+                        BTreeMap::new(),
+                    );
+                }
+            }
+            ActionList::NewTab { name, cwd } => match (name, cwd) {
+                (name, Some(cwd)) => {
+                    new_tabs_with_layout(&self.tab_layout(name.as_deref(), &cwd));
+                }
+                (Some(name), None) => focus_or_create_tab(&name),
+                (None, None) => new_tab(),
+            },
+            ActionList::RenameTab { name, position } => {
+                match position.or(self.zellij_state.focused_tab()) {
+                    // Zellij counts the tabs from 1
+                    Some(position) => rename_tab(position as u32 + 1, name),
+                    None => return Outcome::Failed(String::from("no tab is focused")),
                 }
             }
             ActionList::NewPane { path } => {
@@ -312,19 +1031,50 @@ impl State {
                     open_terminal(path);
                 }
             }
-            ActionList::Run(CommandToRun { path, args, cwd }) => {
-                let (path, args) = match self.search_filter {
-                    EnvironmentFrom::ZellijSession => (path, args),
-                    EnvironmentFrom::DefaultShell => {
-                        let mut a = vec![
-                            "-c".to_string(),
-                            path.to_str().unwrap_or_default().to_string(),
-                        ];
-                        a.append(&mut args.clone());
-
-                        ("fish".into(), a) // TODO: get user’s shell
+            ActionList::Resize {
+                direction,
+                amount,
+                pane,
+            } => {
+                let floats = match self.focus_target(pane) {
+                    Ok(target) => self.pane_floats(Some(target)),
+                    Err(error) => return Outcome::Failed(error),
+                };
+                let resize = if amount < 0 {
+                    Resize::Decrease
+                } else {
+                    Resize::Increase
+                };
+                for _ in 0..amount.unsigned_abs() {
+                    match direction {
+                        Some(direction) => resize_focused_pane_with_direction(resize, direction),
+                        None => resize_focused_pane(resize),
                     }
+                }
+                if floats != Some(true) {
+                    self.focus_console(interface);
+                }
+            }
+            ActionList::Run {
+                command: CommandToRun { path, args, cwd },
+                env,
+                ..
+            } => {
+                // Zellij cannot set the environment of a command, `env` does it for us
+                let (path, args) = if env.is_empty() {
+                    (path, args)
+                } else {
+                    let mut a: Vec<_> = env
+                        .iter()
+                        .map(|(key, value)| format!("{key}={value}"))
+                        .collect();
+                    a.push(path.to_string_lossy().to_string());
+                    a.extend(args);
+
+                    ("env".into(), a)
                 };
+
+                let (path, args) = self.in_environment(path, args);
                 let cmd = CommandToRun { path, args, cwd };
 
                 if self.should_open_floating {
@@ -334,12 +1084,23 @@ impl State {
                 }
             }
 
-            ActionList::HelpAll { selection }
+            ActionList::Job { command, env, .. } => outcome = self.start_job(command, env, None),
+            ActionList::Jobs => outcome = Outcome::Pending,
+            ActionList::ClearJobs => {
+                self.jobs.clear();
+            }
+            ActionList::KillJob { id } => {
+                if let Err(error) = self.jobs.kill(id) {
+                    return Outcome::Failed(error);
+                }
+            }
+
+            ActionList::HelpAll { selection, .. }
             | ActionList::HelpPane { selection }
             | ActionList::HelpPipe { selection } => {
-                done = false;
+                outcome = Outcome::Pending;
 
-                let mut docs: Box<dyn Iterator<Item = ActionList>> = match action {
+                let docs: Box<dyn Iterator<Item = ActionList>> = match action {
                     ActionList::HelpAll { .. } => Box::new(ActionList::filter_any()),
                     ActionList::HelpPane { .. } => Box::new(ActionList::filter_pane()),
                     ActionList::HelpPipe { .. } => Box::new(ActionList::filter_pipe()),
@@ -348,32 +1109,87 @@ impl State {
 
                 match selection {
                     action::Selection::One { row, max: _ } => {
-                        let variant = docs
-                            .nth(row)
-                            .expect("Selection {selection:?} is bounded to the iter size");
-                        let s = variant.get_serializations().first().unwrap_or_else(|| {
-                            panic!("{variant:?} is garanteed to have a serialization string")
+                        let mut names = docs.map(|variant| {
+                            variant
+                                .get_serializations()
+                                .first()
+                                .unwrap_or_else(|| {
+                                    panic!(
+                                        "{variant:?} is garanteed to have a serialization string"
+                                    )
+                                })
+                                .to_string()
                         });
+                        // The aliases are listed after the actions
+                        let name = match &action {
+                            ActionList::HelpAll { aliases, .. } => {
+                                names.chain(aliases.keys().cloned()).nth(row)
+                            }
+                            _ => names.nth(row),
+                        }
+                        .expect("Selection {selection:?} is bounded to the iter size");
 
-                        self.action.set(s, &interface);
+                        self.action.set(&name, interface);
                     }
                     action::Selection::Expand => {}
                 }
             }
-            ActionList::Unknown => {}
-            ActionList::Unavailable { .. } => {
-                // TODO: ring a bell, screenshake, print the same text in red? Something like this.
-                done = false;
+            ActionList::Macro { name, steps } => {
+                if self.running_macros.contains(&name) {
+                    return Outcome::Failed(format!("the macro `{name}` calls itself"));
+                }
+
+                self.running_macros.push(name.clone());
+                for (i, command) in steps.iter().enumerate() {
+                    let sequence = Sequence::parse(command, interface, self.action.context());
+                    let error = match self.run_sequence(&sequence, interface) {
+                        Outcome::Done => continue,
+                        Outcome::Failed(error) => format!("failed: {error}"),
+                        // Nobody can answer it from a macro
+                        Outcome::Pending if self.confirming.take().is_some() => {
+                            String::from("needs a confirmation, add `---yes`")
+                        }
+                        Outcome::Pending => String::from("waits for the user"),
+                    };
+                    outcome =
+                        Outcome::Failed(format!("step {} of the macro `{name}` {error}", i + 1));
+                    break;
+                }
+                self.running_macros.pop();
             }
-        }
 
-        if done {
-            self.action.clear();
+            // TODO: ring a bell, screenshake, print the same text in red? Something like this.
+            ActionList::Unknown { .. } => outcome = Outcome::Failed(String::from("unknown action")),
+            ActionList::Unavailable {
+                calling_interface, ..
+            } => {
+                outcome = Outcome::Failed(format!(
+                    "not available through the `{calling_interface:?}` interface"
+                ))
+            }
+            ActionList::Invalid { error } => outcome = Outcome::Failed(error.message),
+            ActionList::Ambiguous { name, candidates } => {
+                outcome = Outcome::Failed(format!(
+                    "`{name}` is ambiguous, it could be: {}",
+                    candidates.join(", ")
+                ))
+            }
         }
+
+        outcome
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+/// Result of the execution of one action
+enum Outcome {
+    Done,
+    /// The action needs more input from the user (Ex: choosing in the help), the prompt is kept
+    Pending,
+    /// Why the action could not be done
+    Failed(String),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum EnvironmentFrom {
     #[default]
     ZellijSession,
@@ -381,6 +1197,13 @@ pub enum EnvironmentFrom {
 }
 
 impl EnvironmentFrom {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnvironmentFrom::ZellijSession => "ZellijSession",
+            EnvironmentFrom::DefaultShell => "DefaultShell",
+        }
+    }
+
     pub fn progress(&mut self) {
         match self {
             EnvironmentFrom::ZellijSession => *self = EnvironmentFrom::DefaultShell,
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use zellij_tile::prelude::run_command;
+
+use crate::action::token::quote;
+
+/// Marks the `run_command` result that is the user’s `$SHELL`
+pub(crate) const SOURCE: &str = "shell";
+
+/// The shell running the commands with `EnvironmentFrom::DefaultShell`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Shell {
+    /// From the configuration, or the user’s `$SHELL` once it is `detect`ed
+    pub(crate) path: Option<PathBuf>,
+    /// Read the user’s profile, where the `$PATH` is usually set
+    pub(crate) login: bool,
+    /// Read the user’s rc file, where the aliases and functions are usually defined
+    pub(crate) interactive: bool,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell {
+            path: None,
+            login: true,
+            interactive: false,
+        }
+    }
+}
+
+/// The shells whose syntax is not the one of `sh`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Posix,
+    Zsh,
+    Fish,
+    Nu,
+}
+
+impl Kind {
+    fn of(path: &Path) -> Self {
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some("zsh") => Kind::Zsh,
+            Some("fish") => Kind::Fish,
+            Some("nu") => Kind::Nu,
+            _ => Kind::Posix,
+        }
+    }
+
+    /// Quote a word so this shell reads it back as is
+    fn quote(&self, word: &str) -> String {
+        let posix = quote(word);
+        // The words that need no quotes are the same for every shell
+        if posix == word && !(*self == Kind::Zsh && word.starts_with('=')) {
+            return posix;
+        }
+
+        match self {
+            Kind::Posix => posix,
+            // `=word` is replaced by the path of the `word` program
+            Kind::Zsh if posix.starts_with('=') => format!("\\{posix}"),
+            Kind::Zsh => posix,
+            // `\` escapes `\` and `'` even in single quotes
+            Kind::Fish => format!("'{}'", word.replace('\\', r"\\").replace('\'', r"\'")),
+            // Single quotes cannot contain a `'`, double quotes can escape anything
+            Kind::Nu => format!("\"{}\"", word.replace('\\', r"\\").replace('"', "\\\"")),
+        }
+    }
+}
+
+impl Shell {
+    /// The program and arguments running the `words` of a command in this shell
+    pub(crate) fn command(&self, words: &[String]) -> (PathBuf, Vec<String>) {
+        let path = self.path.clone().unwrap_or_else(|| PathBuf::from("sh"));
+        let kind = Kind::of(&path);
+
+        let mut line = words
+            .iter()
+            .map(|word| kind.quote(word))
+            .collect::<Vec<_>>()
+            .join(" ");
+        // Nu runs its own command instead of a program with the same name, like `ls`
+        if kind == Kind::Nu {
+            line.insert(0, '^');
+        }
+
+        let mut args = Vec::new();
+        if self.login {
+            args.push("-l".to_string());
+        }
+        if self.interactive {
+            args.push("-i".to_string());
+        }
+        args.extend(["-c".to_string(), line]);
+
+        (path, args)
+    }
+}
+
+/// Ask the host for the user’s `$SHELL`, the result comes in a `RunCommandResult` event
+pub(crate) fn detect() {
+    let context = BTreeMap::from([("source".to_string(), SOURCE.to_string())]);
+    run_command(&["sh", "-c", r#"printf %s "$SHELL""#], context);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote_all(kind: Kind) -> Vec<String> {
+        [
+            "plain", "a b", "it's", r"a\b", r#"a"b"#, "=ls", "~/x", "$HOME",
+        ]
+        .iter()
+        .map(|word| kind.quote(word))
+        .collect()
+    }
+
+    #[test]
+    fn quote_for_sh() {
+        assert_eq!(
+            quote_all(Kind::Posix),
+            [
+                "plain",
+                "'a b'",
+                r#"'it'"'"'s'"#,
+                r"'a\b'",
+                r#"'a"b'"#,
+                "=ls",
+                "'~/x'",
+                "'$HOME'"
+            ]
+        );
+    }
+
+    #[test]
+    fn quote_for_zsh() {
+        assert_eq!(
+            quote_all(Kind::Zsh),
+            [
+                "plain",
+                "'a b'",
+                r#"'it'"'"'s'"#,
+                r"'a\b'",
+                r#"'a"b'"#,
+                r"\=ls",
+                "'~/x'",
+                "'$HOME'"
+            ]
+        );
+    }
+
+    #[test]
+    fn quote_for_fish() {
+        assert_eq!(
+            quote_all(Kind::Fish),
+            ["plain", "'a b'", r"'it\'s'", r"'a\\b'", r#"'a"b'"#, "=ls", "'~/x'", "'$HOME'"]
+        );
+    }
+
+    #[test]
+    fn quote_for_nu() {
+        assert_eq!(
+            quote_all(Kind::Nu),
+            [
+                "plain",
+                r#""a b""#,
+                r#""it's""#,
+                r#""a\\b""#,
+                r#""a\"b""#,
+                "=ls",
+                r#""~/x""#,
+                r#""$HOME""#
+            ]
+        );
+    }
+
+    #[test]
+    fn command_line() {
+        let words = ["ls".to_string(), "a b".to_string()];
+        let shell = |path: &str, login, interactive| Shell {
+            path: Some(PathBuf::from(path)),
+            login,
+            interactive,
+        };
+
+        let (path, args) = shell("/bin/bash", true, false).command(&words);
+        assert_eq!(path, PathBuf::from("/bin/bash"));
+        assert_eq!(args, ["-l", "-c", "ls 'a b'"]);
+
+        let (_, args) = shell("/usr/bin/nu", false, true).command(&words);
+        assert_eq!(args, ["-i", "-c", r#"^ls "a b""#]);
+
+        let (path, args) = Shell::default().command(&words);
+        assert_eq!(path, PathBuf::from("sh"));
+        assert_eq!(args, ["-l", "-c", "ls 'a b'"]);
+    }
+}
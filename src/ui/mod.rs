@@ -1,22 +1,56 @@
-use std::fmt::{Debug, Display, Formatter};
-use strum::{EnumMessage, EnumProperty};
+use std::fmt::{Display, Formatter};
+use strum::EnumMessage;
 
-use zellij_tile::prelude::{ui_components::*, CommandToRun, FileToOpen, Palette};
+use zellij_tile::prelude::ui_components::*;
+use zellij_tile::prelude::Key;
 
-use crate::action::{ActionList, Interface, Selection};
+use crate::action::sequence::Sequence;
+use crate::action::{ActionList, Interface, ParseError, Selection, MAX_FILES, VARIABLES};
+use crate::jobs::Status;
 use crate::{EnvironmentFrom, State};
 
+const RED: u8 = 9;
 const WHITE: u8 = 15;
-const BLACK: u8 = 16;
+const GREY: u8 = 8;
+
+/// How many completion candidates are listed at once
+const COMPLETION_ROWS: usize = 10;
+/// How many lines of the output of a job are shown at once, also how far `PageUp` and `PageDown` scroll
+pub(crate) const JOB_OUTPUT_ROWS: usize = 15;
 
 const REQUIRED_COLOR: usize = 2;
 const OPTIONAL_COLOR: usize = 3;
-const UNSETTABLE_COLOR: usize = 4;
 
 impl Display for ActionList {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let text = match self {
-            Self::Unknown => {
+            Self::Unknown {
+                name, suggestions, ..
+            } if !suggestions.is_empty() => {
+                let header = format!("Unknown action `{name}`, did you mean:");
+                let header = Text::new(&header).color_range(1, 16..16 + name.len());
+                let items: Vec<_> = suggestions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, suggestion)| {
+                        if i == 0 {
+                            let hint = "<Tab>";
+                            NestedListItem::new(format!("{suggestion} {hint}"))
+                                .color_range(0, suggestion.len() + 1..)
+                                .selected()
+                        } else {
+                            NestedListItem::new(suggestion)
+                        }
+                    })
+                    .collect();
+
+                format!(
+                    "{}\n{}",
+                    serialize_text(&header),
+                    serialize_nested_list(&items)
+                )
+            }
+            Self::Unknown { .. } => {
                 let text = Text::new(r#"Type a command or "help" if you need a list of commands"#)
                     .color_range(1, 19..23);
                 serialize_text(&text)
@@ -60,7 +94,19 @@ impl Display for ActionList {
                     accu + "\n" + &serialize_text(item)
                 })
             }
-            Self::HelpAll { selection }
+            Self::Invalid { error } => styled_text_foreground(RED, &error.message),
+            Self::Ambiguous { name, candidates } => {
+                let header = format!("`{name}` could be any of:");
+                let header = Text::new(&header).color_range(1, 1..1 + name.len());
+                let items: Vec<_> = candidates.iter().map(NestedListItem::new).collect();
+
+                format!(
+                    "{}\n{}",
+                    serialize_text(&header),
+                    serialize_nested_list(&items)
+                )
+            }
+            Self::HelpAll { selection, .. }
             | Self::HelpPane { selection }
             | Self::HelpPipe { selection } => {
                 let docs: Box<dyn Iterator<Item = ActionList>> = match self {
@@ -69,7 +115,7 @@ impl Display for ActionList {
                     Self::HelpPipe { .. } => Box::new(ActionList::filter_pipe()),
                     _ => panic!("Should be one of the help modes"),
                 };
-                let text: Vec<_> = docs
+                let mut text: Vec<_> = docs
                     .enumerate()
                     .flat_map(|(i, variant)| -> Vec<NestedListItem> {
                         let name = variant
@@ -78,8 +124,9 @@ impl Display for ActionList {
                             .expect("At least one serialization is garanteed");
                         let shortcut_msg = "Shortcut variations";
                         let interface_msg = "Interface restriction";
+                        let usage_msg = "Usage";
 
-                        let mut result = Vec::with_capacity(3);
+                        let mut result = Vec::with_capacity(4 + variant.arguments().len());
                         result.push(
                             NestedListItem::new(format!(
                                 "{}:\t{}",
@@ -119,6 +166,27 @@ impl Display for ActionList {
                                         .color_range(0, 0..interface_msg.len()),
                                 );
                             }
+
+                            if !variant.arguments().is_empty() {
+                                result.push(
+                                    NestedListItem::new(format!("{}:\t{}", usage_msg, variant.usage()))
+                                        .indent(1)
+                                        .color_range(2, 0..usage_msg.len()),
+                                );
+                            }
+                            for argument in variant.arguments() {
+                                let usage = argument.usage();
+                                let color = if argument.required {
+                                    REQUIRED_COLOR
+                                } else {
+                                    OPTIONAL_COLOR
+                                };
+                                result.push(
+                                    NestedListItem::new(format!("{}:\t{}", usage, argument.description()))
+                                        .indent(2)
+                                        .color_range(color, 0..usage.len()),
+                                );
+                            }
                         }
 
                         if select {
@@ -129,7 +197,27 @@ impl Display for ActionList {
                     })
                     .collect();
 
-                serialize_nested_list(&text)
+                if let Self::HelpAll { aliases, .. } = self {
+                    let offset = ActionList::filter_any().count();
+                    text.extend(aliases.iter().enumerate().map(|(i, (name, command))| {
+                        let item = NestedListItem::new(format!("{name}:\tAlias for `{command}`"))
+                            .color_range(OPTIONAL_COLOR, 0..name.len());
+                        match selection {
+                            Selection::One { row, max: _ } if *row == offset + i => item.selected(),
+                            _ => item,
+                        }
+                    }));
+                }
+
+                let variables: Vec<_> = VARIABLES
+                    .iter()
+                    .map(|(name, doc)| format!("${name}: {doc}"))
+                    .collect();
+                format!(
+                    "{}\n\nVariables, the cwd of the panes is not known:\n{}",
+                    serialize_nested_list(&text),
+                    variables.join("\n")
+                )
             }
 
             // Self::ClearScreen => String::from("ClearScreen"),
@@ -145,42 +233,89 @@ impl Display for ActionList {
             //     serialize_text(&Text::new("PATH:").color_range(REQUIRED_COLOR, 0..4)),
             //     id.unwrap_or_default() // TODO: not default when unset…
             // ),
-            Self::DetachEveryone => String::from("DetachEveryone"),
-            Self::DetachMe => String::from("DetachMe"),
-            Self::DetachOthers => String::from("DetachOthers"),
-            // Self::EditScrollback => String::from("EditScrollback"),
-            Self::Edit(FileToOpen {
-                path,
-                line_number: line,
-                cwd,
-            }) => format!(
-                "Edit\n{} {:?}\n{} {}\n{} {:?}",
-                serialize_text(&Text::new("PATH:").color_range(REQUIRED_COLOR, 0..4)),
-                path,
-                serialize_text(&Text::new("LINE:").color_range(REQUIRED_COLOR, 0..4)),
-                line.unwrap_or_default(),
-                serialize_text(&Text::new("DIRECTORY:").color_range(UNSETTABLE_COLOR, 0..4)),
-                cwd.clone().unwrap_or_default(),
-            ),
-            Self::NewPane { path } => format!(
-                "New pane\n{} {}",
-                serialize_text(&Text::new("PATH:").color_range(REQUIRED_COLOR, 0..4)),
-                path
-            ),
-            Self::Run(CommandToRun { path, args, cwd }) => format!(
-                "Run\n{} {:?}\n{} {:?}\n{} {:?}",
-                serialize_text(&Text::new("COMMAND:").color_range(REQUIRED_COLOR, 0..7)),
-                path,
-                serialize_text(&Text::new("ARGUMENTS:").color_range(OPTIONAL_COLOR, 0..9)),
-                args,
-                serialize_text(&Text::new("DIRECTORY:").color_range(OPTIONAL_COLOR, 0..9)),
-                cwd.clone().unwrap_or_default(),
-            ),
+            Self::Edit {
+                files, confirmed, ..
+            } => {
+                let mut text = self.render_fields();
+                if files.len() > 1 {
+                    let items: Vec<_> = files
+                        .iter()
+                        .map(|file| NestedListItem::new(file.path.display().to_string()))
+                        .collect();
+                    text = format!("{text}\n{}", serialize_nested_list(&items));
+                }
+                if files.len() > MAX_FILES && !confirmed {
+                    let warning = format!(
+                        "More than {MAX_FILES} files: press Enter twice or add `---yes` to open them"
+                    );
+                    text = format!("{text}\n{}", styled_text_foreground(RED, &warning));
+                }
+
+                text
+            }
+            Self::Focus {
+                panes, selected, ..
+            } => {
+                let fields = self.render_fields();
+                let width = panes
+                    .iter()
+                    .map(|pane| pane.tab.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let items: Vec<_> = panes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, pane)| {
+                        let mut line = format!("{:<width$}  {}", pane.tab, pane.title);
+                        let command_start = line.chars().count() + 2;
+                        let command = pane.command.as_ref().filter(|c| **c != pane.title);
+                        if let Some(command) = command {
+                            line = format!("{line}  {command}");
+                        }
+                        let status_start = line.chars().count() + 1;
+                        let color = match (pane.exited, pane.exit_status) {
+                            (false, _) => None,
+                            (true, Some(code)) => {
+                                line = format!("{line} exit {code}");
+                                Some(if code == 0 { 1 } else { 3 })
+                            }
+                            (true, None) => {
+                                line = format!("{line} exited");
+                                Some(3)
+                            }
+                        };
+
+                        let length = line.chars().count();
+                        let mut item = NestedListItem::new(&line)
+                            .indent(1)
+                            .color_range(2, 0..pane.tab.chars().count())
+                            .color_range(0, command_start.min(length)..status_start.min(length));
+                        if let Some(color) = color {
+                            item = item.color_range(color, status_start..length);
+                        }
+                        if i == *selected {
+                            item.selected()
+                        } else {
+                            item
+                        }
+                    })
+                    .collect();
+
+                if items.is_empty() {
+                    let empty = styled_text_foreground(GREY, "No pane matches");
+                    format!("{fields}\n{empty}")
+                } else {
+                    format!("{fields}\n{}", serialize_nested_list(&items))
+                }
+            }
+            _ => self.render_fields(),
         };
 
         let text = match self {
-            Self::Unknown
+            Self::Unknown { .. }
             | Self::Unavailable { .. }
+            | Self::Invalid { .. }
+            | Self::Ambiguous { .. }
             | Self::HelpAll { .. }
             | Self::HelpPane { .. }
             | Self::HelpPipe { .. } => text,
@@ -197,10 +332,66 @@ impl Display for ActionList {
     }
 }
 
+impl ActionList {
+    /// Actions are displayed from their arguments declaration
+    fn render_fields(&self) -> String {
+        let name = self
+            .get_serializations()
+            .first()
+            .expect("At least one serialization is guaranteed");
+
+        let values = self.values();
+        self.arguments()
+            .iter()
+            .fold(name.to_string(), |accu, argument| {
+                let value = values
+                    .iter()
+                    .find(|(name, _)| *name == argument.name)
+                    .map_or("", |(_, value)| value.as_str());
+                let field = format!("{}:", argument.name.to_uppercase());
+                let color = if argument.required {
+                    REQUIRED_COLOR
+                } else {
+                    OPTIONAL_COLOR
+                };
+                let field = Text::new(&field).color_range(color, 0..field.len() - 1);
+
+                format!("{accu}\n{} {value}", serialize_text(&field))
+            })
+    }
+}
+
+impl Display for Sequence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, step) in self.steps().iter().enumerate() {
+            if i != 0 {
+                let operator = step.condition.as_str();
+                writeln!(
+                    f,
+                    "\n{}",
+                    serialize_text(&Text::new(operator).color_range(1, ..))
+                )?;
+            }
+            write!(f, "{}", step.action)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Display for State {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         // Use the user’s theme
         write!(f, "{}", self.render_action_line())?;
+        write!(f, "{}", self.render_jobs())?;
+        if let Some(error) = &self.last_error {
+            writeln!(
+                f,
+                "{}",
+                styled_text_foreground(RED, &format!("Error: {error}"))
+            )?;
+        }
+        write!(f, "{}", self.render_config_errors())?;
         // TODO: Only print the control line when its options are usefull… or remove it entirely to integrate the options in the command actions
         write!(f, "{}", self.render_controls_line())?;
         Ok(())
@@ -217,20 +408,215 @@ impl State {
         //         c as usize
         //     }
         // };
+        let expanded = match self.action.sequence().expanded() {
+            Some(expanded) => format!(
+                "{} {}\n",
+                serialize_text(&Text::new("EXPANDED:").color_range(1, 0..8)),
+                expanded
+            ),
+            None => String::new(),
+        };
+
+        let errors: Vec<_> = self.action.sequence().errors().collect();
+        let command = self.action.as_str();
+        // An error about something missing at the end of the prompt is shown on the cursor
+        let cursor = if errors.iter().any(|e| e.span.start >= command.len()) {
+            styled_text_underlined(RED, &styled_text_background(WHITE, " "))
+        } else {
+            styled_text_background(WHITE, " ")
+        };
+
+        let autosuggestion = self
+            .action
+            .autosuggestion(&self.history)
+            .map(|rest| styled_text_foreground(GREY, &rest))
+            .unwrap_or_default();
+
         format!(
-            "{} {}{}\n{}\n",
+            "{} {}{}{}\n{}{}{}{}\n",
             serialize_text(&Text::new("PROMPT:").color_range(1, 0..6)),
-            self.action.as_str(),
-            styled_text_background(WHITE, " "), // "Cursor" representation
-            self.action.action(),
+            underline_errors(command, &errors),
+            cursor, // "Cursor" representation
+            autosuggestion,
+            self.render_completion(),
+            self.render_signature(),
+            expanded,
+            self.action.sequence(),
         )
     }
 
+    /// How to write the action, with the argument being written pointed at and documented:
+    /// ```text
+    /// Run <COMMAND> [ARGS...] [---cwd DIR]
+    ///                         ^^^^^^^^^^^^ Directory the program runs in
+    /// ```
+    pub fn render_signature(&self) -> String {
+        let Some((action, current)) = self.action.signature() else {
+            return String::new();
+        };
+        if action.arguments().is_empty() {
+            return String::new();
+        }
+
+        let name = action
+            .get_serializations()
+            .first()
+            .expect("At least one serialization is guaranteed");
+        let mut signature = name.to_string();
+        let mut ranges = Vec::new();
+        let mut pointer = String::new();
+        for argument in action.arguments() {
+            let usage = argument.usage();
+            let color = if argument.required {
+                REQUIRED_COLOR
+            } else {
+                OPTIONAL_COLOR
+            };
+            let start = signature.len() + 1;
+            ranges.push((color, start..start + usage.len()));
+
+            if current == Some(argument) {
+                pointer = format!(
+                    "{}{} {}",
+                    " ".repeat(start),
+                    "^".repeat(usage.len()),
+                    argument.description()
+                );
+            }
+            signature = format!("{signature} {usage}");
+        }
+
+        let text = ranges
+            .into_iter()
+            .fold(Text::new(&signature), |text, (color, range)| {
+                text.color_range(color, range)
+            });
+        let pointer = match pointer.is_empty() {
+            true => String::new(),
+            false => format!("{}\n", serialize_text(&Text::new(pointer))),
+        };
+
+        format!("{}\n{pointer}", serialize_text(&text))
+    }
+
+    /// The candidates to complete the argument being written, around the selected one
+    pub fn render_completion(&self) -> String {
+        let Some(completion) = self.action.completion() else {
+            return String::new();
+        };
+
+        let first = completion
+            .selected
+            .saturating_sub(COMPLETION_ROWS / 2)
+            .min(completion.candidates.len().saturating_sub(COMPLETION_ROWS));
+        let items: Vec<_> = completion
+            .candidates
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(COMPLETION_ROWS)
+            .map(|(i, candidate)| {
+                let item = NestedListItem::new(candidate).indent(1);
+                if i == completion.selected {
+                    item.selected()
+                } else {
+                    item
+                }
+            })
+            .collect();
+
+        format!("{}\n", serialize_nested_list(&items))
+    }
+
+    /// With `Jobs`, the list of the jobs and the output of the selected one. Otherwise only how many there are.
+    pub fn render_jobs(&self) -> String {
+        let jobs = &self.jobs;
+        if !matches!(self.action.sequence().last(), ActionList::Jobs) {
+            return match (jobs.list.len(), jobs.running()) {
+                (0, _) => String::new(),
+                (count, running) => format!(
+                    "{}\n",
+                    styled_text_foreground(
+                        GREY,
+                        &format!("{running} of {count} jobs running, see them with `Jobs`")
+                    )
+                ),
+            };
+        }
+        let Some(selected) = jobs.selected() else {
+            return String::from("No job, start one with `Job <COMMAND>`\n");
+        };
+
+        let items: Vec<_> = jobs
+            .list
+            .iter()
+            .enumerate()
+            .map(|(i, job)| {
+                let status = match job.status {
+                    Status::Running => String::from("running"),
+                    Status::Exited(code) => format!("exit {code}"),
+                    Status::Killed => String::from("killed"),
+                };
+                let line = format!("{:>3} {status:<8} {}", job.id, job.command);
+                let color = match job.status {
+                    Status::Running => 2,
+                    Status::Exited(0) => 1,
+                    Status::Exited(_) | Status::Killed => 3,
+                };
+                let item = NestedListItem::new(&line)
+                    .indent(1)
+                    .color_range(color, 4..4 + status.len());
+                if i == jobs.selected {
+                    item.selected()
+                } else {
+                    item
+                }
+            })
+            .collect();
+
+        let output = selected.output();
+        let last = (jobs.scroll + JOB_OUTPUT_ROWS).min(output.len());
+        let title = match selected.status {
+            Status::Running => format!("Job {} is running…", selected.id),
+            _ if output.is_empty() => format!("Job {} wrote nothing", selected.id),
+            _ => format!(
+                "Output of job {}, lines {}-{last} of {} (<PageUp>/<PageDown> to scroll):",
+                selected.id,
+                jobs.scroll + 1,
+                output.len()
+            ),
+        };
+        let lines: String = output[jobs.scroll.min(last)..last]
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect();
+
+        format!(
+            "{}\n{}\n{lines}",
+            serialize_nested_list(&items),
+            serialize_text(&Text::new(&title).color_range(0, ..))
+        )
+    }
+
+    pub fn render_config_errors(&self) -> String {
+        self.config
+            .errors
+            .iter()
+            .map(|error| format!("{}\n", styled_text_foreground(RED, error)))
+            .collect()
+    }
+
     pub fn render_controls_line(&self) -> String {
         // let has_results = true; // !self.displayed_search_results.1.is_empty();
-        let tiled_floating_control =
-            self.new_floating_control("Ctrl + f", self.should_open_floating);
-        let names_contents_control = self.new_filter_control("Ctrl + e", &self.search_filter);
+        let key = |keys: &[Key]| keys.first().map(|k| k.to_string()).unwrap_or_default();
+        let tiled_floating_control = self.new_floating_control(
+            &key(&self.config.keys.toggle_floating),
+            self.should_open_floating,
+        );
+        let names_contents_control = self.new_filter_control(
+            &key(&self.config.keys.toggle_environment),
+            &self.search_filter,
+        );
 
         serialize_ribbon_line_with_coordinates(
             [tiled_floating_control, names_contents_control],
@@ -241,7 +627,7 @@ impl State {
         )
     }
 
-    fn new_floating_control(&self, key: &'static str, should_open_floating: bool) -> Text {
+    fn new_floating_control(&self, key: &str, should_open_floating: bool) -> Text {
         if should_open_floating {
             Text::new(format!("<{}> OPEN FLOATING", key)).color_range(0, 1..=key.len())
         } else {
@@ -249,7 +635,7 @@ impl State {
         }
     }
 
-    fn new_filter_control(&self, key: &'static str, search_filter: &EnvironmentFrom) -> Text {
+    fn new_filter_control(&self, key: &str, search_filter: &EnvironmentFrom) -> Text {
         match search_filter {
             EnvironmentFrom::ZellijSession => {
                 Text::new(format!("<{}> ZELLIJ’S ENVIRONMENT", key)).color_range(0, 1..=key.len())
@@ -261,17 +647,52 @@ impl State {
     }
 }
 
-pub fn bold(text: &str) -> String {
-    format!("\u{1b}[1m{}\u{1b}[m", text)
+/// Underline the parts of the `command` the `errors` point to
+fn underline_errors(command: &str, errors: &[&ParseError]) -> String {
+    let mut spans: Vec<_> = errors
+        .iter()
+        .map(|e| e.span.clone())
+        .filter(|span| !span.is_empty() && span.end <= command.len())
+        .collect();
+    spans.sort_by_key(|span| span.start);
+
+    let mut result = String::with_capacity(command.len());
+    let mut position = 0;
+    for span in spans {
+        // Overlapping spans come from the same alias, underline it once
+        if span.start < position {
+            continue;
+        }
+        result.push_str(&command[position..span.start]);
+        result.push_str(&styled_text_underlined(RED, &command[span.clone()]));
+        position = span.end;
+    }
+    result.push_str(&command[position..]);
+
+    result
 }
 
-pub fn styled_text(foreground_color: u8, background_color: u8, text: &str) -> String {
+/// The `error` below the `command`, with carets under the part it points to:
+/// ```text
+/// Run 'cargo
+///     ^^^^^^ Unterminated single quote
+/// ```
+pub fn render_parse_error(command: &str, error: &ParseError) -> String {
+    let start = command.get(..error.span.start).unwrap_or(command);
+    let offending = command.get(error.span.clone()).unwrap_or_default();
+
     format!(
-        "\u{1b}[38;5;{};48;5;{}m{}\u{1b}[m",
-        foreground_color, background_color, text
+        "{command}\n{}{} {}",
+        " ".repeat(start.chars().count()),
+        "^".repeat(offending.chars().count().max(1)),
+        error.message
     )
 }
 
+pub fn styled_text_underlined(foreground_color: u8, text: &str) -> String {
+    format!("\u{1b}[4;38;5;{}m{}\u{1b}[m", foreground_color, text)
+}
+
 pub fn styled_text_foreground(foreground_color: u8, text: &str) -> String {
     format!("\u{1b}[38;5;{}m{}\u{1b}[m", foreground_color, text)
 }